@@ -1,76 +1,26 @@
 use super::core::*;
+use super::discrete_log::*;
 use super::*;
 use alloc::vec::*;
 use curve25519_dalek::edwards::*;
 use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::IsIdentity;
 use curve25519_dalek::traits::MultiscalarMul;
+use curve25519_dalek::traits::VartimeMultiscalarMul;
 
 use sha2::Sha256;
+use sha2::Sha512;
 use sha2::Digest;
 use rand_core::RngCore;
 use rand_core::OsRng;
 use serde::*;
 
-const RANGE_SIZE: usize = 64;
+pub(crate) const RANGE_SIZE: usize = 64;
 
-struct DLTable {
-    array: Vec<Vec<(CompressedEdwardsY, u64)>>,
-}
-impl DLTable {
-    pub fn new() -> DLTable {
-        let mut list = Vec::new();
-        for _ in 0..(1 << 20) {
-            list.push(Vec::<(CompressedEdwardsY, u64)>::new());
-        }
-
-        DLTable { array: list }
-    }
-
-    pub fn insert(&mut self, V: CompressedEdwardsY, i: u64) {
-        let b = V.as_bytes();
-        let index = ((b[29] as usize & 0xf) << 16) | ((b[30] as usize) << 8) | (b[31] as usize);
-        self.array[index].push((V, i));
-    }
-
-    pub fn get(&mut self, V: &CompressedEdwardsY) -> Option<u64> {
-        let b = V.as_bytes();
-        let index = ((b[29] as usize & 0xf) << 16) | ((b[30] as usize) << 8) | (b[31] as usize);
-        let list = &self.array[index];
-        for (W, i) in list {
-            if *V == *W {
-                return Some(*i);
-            }
-        }
-        return None;
-    }
-}
-
-pub fn discrete_log_2n_bit(n: u64, G: EdwardsPoint, H: EdwardsPoint) -> u64 {
-    let two_to_the_n: u64 = 1 << n;
-    let mut saved_map = DLTable::new();
-    let mut V = EdwardsPoint::default();
-
-    //Generate table
-    for j in 0..two_to_the_n {
-        let C = V.compress();
-        saved_map.insert(C, j);
-        V = V + G;
-    }
-    //V = Scalar::from(two_to_the_20) * G;
-    let mut S = H;
-    for i in 0..two_to_the_n {
-        let C = S.compress();
-        match saved_map.get(&C) {
-            Some(j) => {
-                return (i << n) + j;
-            }
-            None => {
-                S = S - V;
-            }
-        }
-    }
-
-    return 0;
+//Recovers a value of at most 2n bits from H = v*G, see DiscreteLogTable to reuse the table.
+#[allow(non_snake_case)]
+pub fn discrete_log_2n_bit(n: u64, G: EdwardsPoint, H: EdwardsPoint) -> Option<u64> {
+    return DiscreteLogTable::new(G, n as u32, 2 * n as u32).get(&H);
 }
 
 pub fn inner_product(a: &[Scalar], b: &[Scalar]) -> Scalar {
@@ -82,7 +32,7 @@ pub fn inner_product(a: &[Scalar], b: &[Scalar]) -> Scalar {
     return sum;
 }
 
-fn add_mult_ed(x: Scalar, a: &[EdwardsPoint], y: Scalar, b: &[EdwardsPoint]) -> Vec<EdwardsPoint> {
+pub(crate) fn add_mult_ed(x: Scalar, a: &[EdwardsPoint], y: Scalar, b: &[EdwardsPoint]) -> Vec<EdwardsPoint> {
     assert_eq!(a.len(), b.len());
     let mut output = vec![EdwardsPoint::default(); a.len()];
     for i in 0..a.len() {
@@ -91,7 +41,7 @@ fn add_mult_ed(x: Scalar, a: &[EdwardsPoint], y: Scalar, b: &[EdwardsPoint]) ->
     return output;
 }
 
-fn mult_ed(x: &[Scalar], a: &[EdwardsPoint]) -> Vec<EdwardsPoint> {
+pub(crate) fn mult_ed(x: &[Scalar], a: &[EdwardsPoint]) -> Vec<EdwardsPoint> {
     assert_eq!(x.len(), a.len());
     let mut output = vec![EdwardsPoint::default(); a.len()];
     for i in 0..a.len() {
@@ -127,7 +77,7 @@ pub fn multiscalar_mul_add(
     return EdwardsPoint::multiscalar_mul(&s, &v);
 }
 
-fn add_mult(x: Scalar, a: &[Scalar], y: Scalar, b: &[Scalar]) -> Vec<Scalar> {
+pub(crate) fn add_mult(x: Scalar, a: &[Scalar], y: Scalar, b: &[Scalar]) -> Vec<Scalar> {
     assert_eq!(a.len(), b.len());
     let mut output = vec![Scalar::zero(); a.len()];
     for i in 0..a.len() {
@@ -144,6 +94,40 @@ pub struct BulletProof {
     b: Scalar,
 }
 
+impl BulletProof {
+    //L and R of every round, then a and b, 32 bytes each
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(32 * (2 * self.Ls.len() + 2));
+        for i in 0..self.Ls.len() {
+            bytes.extend_from_slice(&self.Ls[i].compress().to_bytes());
+            bytes.extend_from_slice(&self.Rs[i].compress().to_bytes());
+        }
+        bytes.extend_from_slice(self.a.as_bytes());
+        bytes.extend_from_slice(self.b.as_bytes());
+        return bytes;
+    }
+
+    #[allow(non_snake_case)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<BulletProof, DecodeError> {
+        if bytes.len() % 64 != 0 || bytes.len() == 0 {
+            return Err(DecodeError::WrongLength);
+        }
+        let rounds = bytes.len() / 64 - 1;
+        let mut Ls = Vec::with_capacity(rounds);
+        let mut Rs = Vec::with_capacity(rounds);
+        for i in 0..rounds {
+            Ls.push(read_point(&bytes[64 * i..])?);
+            Rs.push(read_point(&bytes[64 * i + 32..])?);
+        }
+        return Ok(BulletProof {
+            Ls,
+            Rs,
+            a: read_scalar(&bytes[64 * rounds..])?,
+            b: read_scalar(&bytes[64 * rounds + 32..])?,
+        });
+    }
+}
+
 pub fn fake_bullet_proof() -> BulletProof {
     return BulletProof {
         Ls: vec![],
@@ -246,6 +230,93 @@ pub fn get_hash(L: &EdwardsPoint, R: &EdwardsPoint) -> Scalar {
     return x;
 }
 
+//Running Sha256 over everything the verifier has seen, every challenge is fed back into it.
+pub(crate) struct Transcript {
+    hasher: Sha256,
+}
+
+impl Transcript {
+    pub(crate) fn new(label: &[u8]) -> Transcript {
+        let mut hasher = Sha256::new();
+        hasher.input(label);
+        Transcript { hasher }
+    }
+
+    pub(crate) fn append_u64(&mut self, x: u64) {
+        self.hasher.input(&x.to_le_bytes());
+    }
+
+    pub(crate) fn append_point(&mut self, point: &EdwardsPoint) {
+        self.hasher.input(&point.compress().to_bytes());
+    }
+
+    pub(crate) fn append_scalar(&mut self, scalar: &Scalar) {
+        self.hasher.input(scalar.as_bytes());
+    }
+
+    pub(crate) fn challenge(&mut self) -> Scalar {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(self.hasher.clone().result().as_slice());
+        let x = Scalar::from_bytes_mod_order(hash);
+        self.append_scalar(&x);
+        return x;
+    }
+}
+
+#[allow(non_snake_case)]
+pub fn verify_bulletproof(
+    n: usize,
+    g: &[EdwardsPoint],
+    h: &[EdwardsPoint],
+    u: EdwardsPoint,
+    P: EdwardsPoint,
+    proof: &BulletProof,
+) -> bool {
+    return verify_bulletproof_hmul(n, g, h, &array_of(Scalar::one(), n), u, P, proof);
+}
+
+//Checks a*<s,g> + b*<s^-1*hmul,h> + a*b*u - P - sum(x^2*L + x^-2*R) = 0 with a single
+//multiscalar multiplication, where s are the products of the challenges that fold g.
+#[allow(non_snake_case)]
+pub fn verify_bulletproof_hmul(
+    n: usize,
+    g: &[EdwardsPoint],
+    h: &[EdwardsPoint],
+    hmul: &[Scalar],
+    u: EdwardsPoint,
+    P: EdwardsPoint,
+    proof: &BulletProof,
+) -> bool {
+    if g.len() < n || h.len() < n || hmul.len() < n {
+        return false;
+    }
+    let (x_sq, x_inv_sq, s, s_) = match bulletproof_verification_scalars(n, proof) {
+        Some(scalars) => scalars,
+        None => return false,
+    };
+    let a = proof.a;
+    let b = proof.b;
+
+    let scalars = s
+        .iter()
+        .map(|s_i| a * s_i)
+        .chain(s_.iter().zip(hmul.iter()).map(|(s_i, m)| b * s_i * m))
+        .chain(Some(a * b))
+        .chain(Some(-Scalar::one()))
+        .chain(x_sq.iter().map(|x| -x))
+        .chain(x_inv_sq.iter().map(|x| -x));
+    let points = g[0..n]
+        .iter()
+        .chain(h[0..n].iter())
+        .chain(Some(&u))
+        .chain(Some(&P))
+        .chain(proof.Ls.iter())
+        .chain(proof.Rs.iter());
+
+    return EdwardsPoint::vartime_multiscalar_mul(scalars, points).is_identity();
+}
+
+#[cfg(test)]
 fn alt_mult(x: Scalar, v: &mut [Scalar], b: usize) {
     let x_ = x.invert();
     let mut up = false;
@@ -261,8 +332,10 @@ fn alt_mult(x: Scalar, v: &mut [Scalar], b: usize) {
     }
 }
 
+//The straightforward verifier, kept to cross check verify_bulletproof in the tests.
+#[cfg(test)]
 #[allow(non_snake_case)]
-pub fn verify_bulletproof(
+fn verify_bulletproof_reference(
     n: usize,
     g: &[EdwardsPoint],
     h: &[EdwardsPoint],
@@ -306,8 +379,9 @@ pub fn verify_bulletproof(
     return (P_) == P + Lmul + Rmul;
 }
 
+#[cfg(test)]
 #[allow(non_snake_case)]
-pub fn verify_bulletproof_hmul(
+fn verify_bulletproof_hmul_reference(
     n: usize,
     g: &[EdwardsPoint],
     h: &[EdwardsPoint],
@@ -352,14 +426,14 @@ pub fn verify_bulletproof_hmul(
     return (P_) == P + Lmul + Rmul;
 }
 
-fn fill_random_scalars(arr: &mut [Scalar]) {
+pub(crate) fn fill_random_scalars(arr: &mut [Scalar]) {
     let mut csprng: OsRng = OsRng::default();
     for i in 0..arr.len() {
         arr[i] = Scalar::random(&mut csprng);
     }
 }
 
-fn scalars_array_from_bits(v: u64) -> [Scalar; RANGE_SIZE] {
+pub(crate) fn scalars_array_from_bits(v: u64) -> [Scalar; RANGE_SIZE] {
     let mut arr = [Scalar::default(); RANGE_SIZE];
     let mut w = v;
     for i in 0..RANGE_SIZE {
@@ -391,7 +465,7 @@ fn add_scalar_to_array(a: &[Scalar], b: Scalar) -> Vec<Scalar> {
     return arr;
 }
 
-fn multiply_scalar_to_array(a: &[Scalar], b: Scalar) -> Vec<Scalar> {
+pub(crate) fn multiply_scalar_to_array(a: &[Scalar], b: Scalar) -> Vec<Scalar> {
     let mut arr = vec![Scalar::default(); a.len()];
     for i in 0..a.len() {
         arr[i] = a[i] * b;
@@ -399,7 +473,7 @@ fn multiply_scalar_to_array(a: &[Scalar], b: Scalar) -> Vec<Scalar> {
     return arr;
 }
 
-fn array_of(z: Scalar, n: usize) -> Vec<Scalar> {
+pub(crate) fn array_of(z: Scalar, n: usize) -> Vec<Scalar> {
     let mut arr = vec![Scalar::default(); n];
     for i in 0..n {
         arr[i] = z;
@@ -407,7 +481,7 @@ fn array_of(z: Scalar, n: usize) -> Vec<Scalar> {
     return arr;
 }
 
-fn add_scalar_arrays(a: &[Scalar], b: &[Scalar]) -> Vec<Scalar> {
+pub(crate) fn add_scalar_arrays(a: &[Scalar], b: &[Scalar]) -> Vec<Scalar> {
     let mut arr = vec![Scalar::default(); a.len()];
     for i in 0..a.len() {
         arr[i] = a[i] + b[i];
@@ -415,7 +489,7 @@ fn add_scalar_arrays(a: &[Scalar], b: &[Scalar]) -> Vec<Scalar> {
     return arr;
 }
 
-fn substract_scalar_arrays(a: &[Scalar], b: &[Scalar]) -> Vec<Scalar> {
+pub(crate) fn substract_scalar_arrays(a: &[Scalar], b: &[Scalar]) -> Vec<Scalar> {
     let mut arr = vec![Scalar::default(); a.len()];
     for i in 0..a.len() {
         arr[i] = a[i] - b[i];
@@ -431,7 +505,7 @@ pub fn multiply_scalar_arrays(a: &[Scalar], b: &[Scalar]) -> Vec<Scalar> {
     return arr;
 }
 
-fn to_the_n(x: Scalar, n: usize) -> Vec<Scalar> {
+pub(crate) fn to_the_n(x: Scalar, n: usize) -> Vec<Scalar> {
     let mut arr = vec![Scalar::default(); n];
     let mut prod = Scalar::one();
     for i in 0..n {
@@ -459,19 +533,71 @@ fn to_the_n_multi_var(x: Scalar, z: Scalar, n: usize, m: usize) -> Vec<Scalar> {
 #[allow(non_snake_case)]
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct BulletRangeProof {
-    A: EdwardsPoint,
-    SS: EdwardsPoint,
-    T1: EdwardsPoint,
-    T2: EdwardsPoint,
-    tao_x: Scalar,
-    mu: Scalar,
-    t_cap: Scalar,
+    pub(crate) A: EdwardsPoint,
+    pub(crate) SS: EdwardsPoint,
+    pub(crate) T1: EdwardsPoint,
+    pub(crate) T2: EdwardsPoint,
+    pub(crate) tao_x: Scalar,
+    pub(crate) mu: Scalar,
+    pub(crate) t_cap: Scalar,
     pub V: Vec<EdwardsPoint>,
-    bullet_proof: BulletProof,
+    pub(crate) bullet_proof: BulletProof,
 }
 
-pub fn bullet_range_verify(proof: &BulletRangeProof, bases: Bases) -> bool {
-    return bullet_range_verify_ex(proof, &bases, None, Scalar::zero());
+impl BulletRangeProof {
+    //The length of to_bytes for m values
+    pub fn serialized_size(m: usize) -> usize {
+        let rounds = (RANGE_SIZE * to_2s_power(m)).trailing_zeros() as usize;
+        return 32 * (7 + 2 * rounds + 2 + m);
+    }
+
+    //A, SS, T1, T2, tao_x, mu, t_cap, the inner product proof and the commitments V, 32 bytes
+    //each. The number of values is implied by the length.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(BulletRangeProof::serialized_size(self.V.len()));
+        for point in [self.A, self.SS, self.T1, self.T2].iter() {
+            bytes.extend_from_slice(&point.compress().to_bytes());
+        }
+        for scalar in [self.tao_x, self.mu, self.t_cap].iter() {
+            bytes.extend_from_slice(scalar.as_bytes());
+        }
+        bytes.extend_from_slice(&self.bullet_proof.to_bytes());
+        for point in &self.V {
+            bytes.extend_from_slice(&point.compress().to_bytes());
+        }
+        return bytes;
+    }
+
+    #[allow(non_snake_case)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<BulletRangeProof, DecodeError> {
+        let mut m = 1;
+        while BulletRangeProof::serialized_size(m) < bytes.len() {
+            m += 1;
+        }
+        if BulletRangeProof::serialized_size(m) != bytes.len() {
+            return Err(DecodeError::WrongLength);
+        }
+        let ipa_end = bytes.len() - 32 * m;
+        let mut V = Vec::with_capacity(m);
+        for j in 0..m {
+            V.push(read_point(&bytes[ipa_end + 32 * j..])?);
+        }
+        return Ok(BulletRangeProof {
+            A: read_point(&bytes[0..])?,
+            SS: read_point(&bytes[32..])?,
+            T1: read_point(&bytes[64..])?,
+            T2: read_point(&bytes[96..])?,
+            tao_x: read_scalar(&bytes[128..])?,
+            mu: read_scalar(&bytes[160..])?,
+            t_cap: read_scalar(&bytes[192..])?,
+            V,
+            bullet_proof: BulletProof::from_bytes(&bytes[224..ipa_end])?,
+        });
+    }
+}
+
+pub fn bullet_range_verify(proof: &BulletRangeProof, bases: &Bases) -> bool {
+    return bullet_range_verify_ex(proof, bases, None, Scalar::zero());
 }
 
 #[allow(non_snake_case)]
@@ -481,30 +607,82 @@ pub fn bullet_range_verify_ex(
     extra_hash_input: Option<(&[u8], &EdwardsPoint, &EdwardsPoint)>,
     other_hash: Scalar,
 ) -> bool {
-    let m = proof.V.len();
-    let gs: Vec<EdwardsPoint> = bases.Gs[0..m * RANGE_SIZE].iter().map(|x| *x).collect();
-    let hs: Vec<EdwardsPoint> = bases.Hs[0..m * RANGE_SIZE].iter().map(|x| *x).collect();
-    let g = bases.GInit;
-    let h = bases.HInit;
+    return bullet_range_verify_ex_detailed(proof, bases, extra_hash_input, other_hash).is_ok();
+}
+
+//The sub-check of a range proof that failed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RangeProofError {
+    NoValues,
+    Bases(BasesError),
+    //t(x) = t_cap does not match V, T1 and T2
+    PolynomialCheck,
+    InnerProductCheck,
+}
+
+//bullet_range_verify_ex telling which check failed
+#[allow(non_snake_case)]
+pub fn bullet_range_verify_ex_detailed(
+    proof: &BulletRangeProof,
+    bases: &Bases,
+    extra_hash_input: Option<(&[u8], &EdwardsPoint, &EdwardsPoint)>,
+    other_hash: Scalar,
+) -> Result<(), RangeProofError> {
     let x = match extra_hash_input {
         Some((message, R, P)) => {
             get_hash_of_data_and_points(&message, &[&proof.T1, &proof.T2, R, P]) - other_hash
         }
         None => get_hash(&proof.T1, &proof.T2),
     };
+    return bullet_range_verify_challenge_detailed(proof, bases, x);
+}
+
+pub fn bullet_range_verify_detailed(
+    proof: &BulletRangeProof,
+    bases: &Bases,
+) -> Result<(), RangeProofError> {
+    return bullet_range_verify_ex_detailed(proof, bases, None, Scalar::zero());
+}
+
+//Checks the proof for the challenge x, which is a hash of T1 and T2 unless the proof is a
+//branch of an OR proof.
+pub(crate) fn bullet_range_verify_challenge(
+    proof: &BulletRangeProof,
+    bases: &Bases,
+    x: Scalar,
+) -> bool {
+    return bullet_range_verify_challenge_detailed(proof, bases, x).is_ok();
+}
+
+#[allow(non_snake_case)]
+fn bullet_range_verify_challenge_detailed(
+    proof: &BulletRangeProof,
+    bases: &Bases,
+    x: Scalar,
+) -> Result<(), RangeProofError> {
+    //The prover pads the values to a power of 2 with zero values and zero blindings. Those
+    //padding commitments are the identity, so they are reconstructed here instead of being
+    //carried in the proof.
+    let num_values = proof.V.len();
+    if num_values == 0 {
+        return Err(RangeProofError::NoValues);
+    }
+    bases
+        .check_capacity(num_values)
+        .map_err(RangeProofError::Bases)?;
+    let m = to_2s_power(num_values);
+    let gs: Vec<EdwardsPoint> = bases.Gs[0..m * RANGE_SIZE].iter().map(|x| *x).collect();
+    let hs: Vec<EdwardsPoint> = bases.Hs[0..m * RANGE_SIZE].iter().map(|x| *x).collect();
+    let g = bases.GInit;
+    let h = bases.HInit;
     let y = get_hash(&proof.A, &proof.SS);
     let z = get_hash(&proof.SS, &proof.A);
     let ymn = to_the_n(y, RANGE_SIZE * m);
     let z_m = to_the_n(z, m);
-    let twon = to_the_n(Scalar::from(2u64), RANGE_SIZE);
-    let onen = to_the_n(Scalar::from(1u64), RANGE_SIZE);
-    let one_m = to_the_n(Scalar::from(1u64), m);
-    let mut _z2_z_m = multiply_scalar_to_array(&z_m, -z * z);
+    let mut _z2_z_m = multiply_scalar_to_array(&z_m[0..num_values], -z * z);
     let twon_z_pow = to_the_n_multi_var(Scalar::from(2u64), z, RANGE_SIZE, m);
 
-    let one_mn = array_of(Scalar::from(1u64), RANGE_SIZE * m);
-    let sig = (z - z * z) * inner_product(&one_mn, &ymn)
-        - z * z * z * inner_product(&onen, &twon) * inner_product(&z_m, &one_m);
+    let sig = range_proof_delta(y, z, m);
 
     let mut mult_scalars = vec![proof.t_cap, proof.tao_x, -sig, -x, -x * x];
     mult_scalars.append(&mut _z2_z_m);
@@ -515,7 +693,7 @@ pub fn bullet_range_verify_ex(
         EdwardsPoint::multiscalar_mul(&mut mult_scalars.into_iter(), &mut mult_points.into_iter());
 
     if sum != EdwardsPoint::default() {
-        return false;
+        return Err(RangeProofError::PolynomialCheck);
     }
 
     let y_mn = &to_the_n(y.invert(), RANGE_SIZE * m);
@@ -532,10 +710,208 @@ pub fn bullet_range_verify_ex(
 
     let P_ = P - proof.mu * h + proof.t_cap * h;
 
-    return verify_bulletproof_hmul(RANGE_SIZE * m, &gs, &hs, &y_mn, h, P_, &proof.bullet_proof);
+    if !verify_bulletproof_hmul(RANGE_SIZE * m, &gs, &hs, &y_mn, h, P_, &proof.bullet_proof) {
+        return Err(RangeProofError::InnerProductCheck);
+    }
+    return Ok(());
+}
+
+//(z-z^2)*<1,y^mn> - z^3*<1,2^n>*<z^m,1>
+fn range_proof_delta(y: Scalar, z: Scalar, m: usize) -> Scalar {
+    let ymn = to_the_n(y, RANGE_SIZE * m);
+    let z_m = to_the_n(z, m);
+    let twon = to_the_n(Scalar::from(2u64), RANGE_SIZE);
+    let onen = to_the_n(Scalar::from(1u64), RANGE_SIZE);
+    let one_m = to_the_n(Scalar::from(1u64), m);
+    let one_mn = array_of(Scalar::from(1u64), RANGE_SIZE * m);
+    return (z - z * z) * inner_product(&one_mn, &ymn)
+        - z * z * z * inner_product(&onen, &twon) * inner_product(&z_m, &one_m);
+}
+
+//Returns the squared challenges, their squared inverses and the scalars s such that the
+//folded G of the proof is <s, g> and the folded H is <s^-1, h>. In round j the lower half
+//of g is multiplied by x_j^-1 and the upper half by x_j, so s_i is the product of x_j^-1 or
+//x_j chosen by the bits of i, the most significant bit belonging to the first round.
+#[allow(non_snake_case)]
+fn bulletproof_verification_scalars(
+    n: usize,
+    proof: &BulletProof,
+) -> Option<(Vec<Scalar>, Vec<Scalar>, Vec<Scalar>, Vec<Scalar>)> {
+    let Ls = &proof.Ls;
+    let Rs = &proof.Rs;
+    let rounds = Ls.len();
+    if Rs.len() != rounds || rounds >= 64 || n != 1 << rounds {
+        return None;
+    }
+    let x: Vec<Scalar> = (0..rounds).map(|j| get_hash(&Ls[j], &Rs[j])).collect();
+    return Some(folding_scalars(n, x));
+}
+
+//The scalars of bulletproof_verification_scalars for the challenges x of the rounds in order,
+//n must be 2^x.len().
+pub(crate) fn folding_scalars(
+    n: usize,
+    x: Vec<Scalar>,
+) -> (Vec<Scalar>, Vec<Scalar>, Vec<Scalar>, Vec<Scalar>) {
+    let rounds = x.len();
+    let mut x_inv = x.clone();
+    let all_x_inv = Scalar::batch_invert(&mut x_inv);
+    let x_sq: Vec<Scalar> = x.iter().map(|x| x * x).collect();
+    let x_inv_sq: Vec<Scalar> = x_inv.iter().map(|x| x * x).collect();
+    let all_x = x.iter().fold(Scalar::one(), |acc, x| acc * x);
+
+    let mut s = vec![all_x_inv; n];
+    let mut s_ = vec![all_x; n];
+    for i in 1..n {
+        //i differs from i-k only in its highest bit, which turns x_j^-1 into x_j
+        let lg_i = (usize::BITS - 1 - i.leading_zeros()) as usize;
+        let k = 1 << lg_i;
+        let j = rounds - 1 - lg_i;
+        s[i] = s[i - k] * x_sq[j];
+        s_[i] = s_[i - k] * x_inv_sq[j];
+    }
+    return (x_sq, x_inv_sq, s, s_);
+}
+
+//Collects the terms of several verification equations so that they are all checked by a
+//single multiscalar multiplication. The scalars of the bases shared by every proof are
+//accumulated, the points that belong to a single proof are appended.
+struct BatchEquation {
+    g: Scalar,
+    h: Scalar,
+    gs: Vec<Scalar>,
+    hs: Vec<Scalar>,
+    scalars: Vec<Scalar>,
+    points: Vec<EdwardsPoint>,
 }
 
-fn to_2s_power(y: usize) -> usize {
+impl BatchEquation {
+    fn new() -> BatchEquation {
+        BatchEquation {
+            g: Scalar::zero(),
+            h: Scalar::zero(),
+            gs: Vec::new(),
+            hs: Vec::new(),
+            scalars: Vec::new(),
+            points: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, scalar: Scalar, point: EdwardsPoint) {
+        self.scalars.push(scalar);
+        self.points.push(point);
+    }
+
+    //Adds both equations of bullet_range_verify_ex for the challenge x, the first one
+    //multiplied by w1 and the inner product one by w2. Returns false for a malformed proof.
+    #[allow(non_snake_case)]
+    fn add_range_proof(
+        &mut self,
+        proof: &BulletRangeProof,
+        x: Scalar,
+        w1: Scalar,
+        w2: Scalar,
+    ) -> bool {
+        let num_values = proof.V.len();
+        if num_values == 0 {
+            return false;
+        }
+        let m = to_2s_power(num_values);
+        let n = RANGE_SIZE * m;
+        let (x_sq, x_inv_sq, s, s_) = match bulletproof_verification_scalars(n, &proof.bullet_proof)
+        {
+            Some(scalars) => scalars,
+            None => return false,
+        };
+        let y = get_hash(&proof.A, &proof.SS);
+        let z = get_hash(&proof.SS, &proof.A);
+        let z_m = to_the_n(z, m);
+
+        //t_cap*g + tao_x*h = sig*g + x*T1 + x^2*T2 + sum(z^(2+j)*V_j)
+        self.g += w1 * (proof.t_cap - range_proof_delta(y, z, m));
+        self.h += w1 * proof.tao_x;
+        self.push(-w1 * x, proof.T1);
+        self.push(-w1 * x * x, proof.T2);
+        for j in 0..num_values {
+            self.push(-w1 * z * z * z_m[j], proof.V[j]);
+        }
+
+        //a*<s,g> + b*<s^-1*y^-n,h> + a*b*h = P - mu*h + t_cap*h + sum(x^2*L + x^-2*R)
+        //where P = A + x*SS - z*<1,g> + <z + z^(2+j)*2^n*y^-n, h>
+        let a = proof.bullet_proof.a;
+        let b = proof.bullet_proof.b;
+        let y_mn = to_the_n(y.invert(), n);
+        let twon_z_pow = to_the_n_multi_var(Scalar::from(2u64), z, RANGE_SIZE, m);
+        if self.gs.len() < n {
+            self.gs.resize(n, Scalar::zero());
+            self.hs.resize(n, Scalar::zero());
+        }
+        for i in 0..n {
+            self.gs[i] += w2 * (a * s[i] + z);
+            self.hs[i] += w2 * (y_mn[i] * (b * s_[i] - twon_z_pow[i]) - z);
+        }
+        self.h += w2 * (a * b + proof.mu - proof.t_cap);
+        self.push(-w2, proof.A);
+        self.push(-w2 * x, proof.SS);
+        for j in 0..x_sq.len() {
+            self.push(-w2 * x_sq[j], proof.bullet_proof.Ls[j]);
+            self.push(-w2 * x_inv_sq[j], proof.bullet_proof.Rs[j]);
+        }
+        return true;
+    }
+
+    fn is_zero(&self, bases: &Bases) -> bool {
+        let n = self.gs.len();
+        if n > bases.num_generators() {
+            return false;
+        }
+        let shared_scalars = [self.g, self.h];
+        let shared_points = [bases.GInit, bases.HInit];
+        let scalars = shared_scalars
+            .iter()
+            .chain(self.gs.iter())
+            .chain(self.hs.iter())
+            .chain(self.scalars.iter());
+        let points = shared_points
+            .iter()
+            .chain(bases.Gs[0..n].iter())
+            .chain(bases.Hs[0..n].iter())
+            .chain(self.points.iter());
+        return EdwardsPoint::vartime_multiscalar_mul(scalars, points).is_identity();
+    }
+}
+
+//Verifies many range proofs with a single multiscalar multiplication. Every verification
+//equation is multiplied by a fresh random weight before they are summed, so a batch holding
+//an invalid proof passes only with negligible probability. If the batch fails the proofs are
+//verified one by one and the index of the first invalid one is returned.
+pub fn bullet_range_verify_batch(proofs: &[&BulletRangeProof], bases: &Bases) -> Result<(), usize> {
+    let mut csprng: OsRng = OsRng::default();
+    let mut equation = BatchEquation::new();
+    let mut well_formed = true;
+    for proof in proofs {
+        let x = get_hash(&proof.T1, &proof.T2);
+        let w1 = Scalar::random(&mut csprng);
+        let w2 = Scalar::random(&mut csprng);
+        if !equation.add_range_proof(proof, x, w1, w2) {
+            well_formed = false;
+            break;
+        }
+    }
+
+    if well_formed && equation.is_zero(bases) {
+        return Ok(());
+    }
+
+    for i in 0..proofs.len() {
+        if !bullet_range_verify_ex(proofs[i], bases, None, Scalar::zero()) {
+            return Err(i);
+        }
+    }
+    return Ok(());
+}
+
+pub(crate) fn to_2s_power(y: usize) -> usize {
     let mut num_ones = 0;
     let mut pows = 1usize;
     let mut x = y;
@@ -576,17 +952,68 @@ fn to_2s_pow_scalar_vec(g: &[Scalar]) -> Vec<Scalar> {
 #[allow(non_snake_case)]
 pub fn bullet_range_proof(
     gamma: &[Scalar],
-    v: &[u64], //the length of v must be a power of 2 at this point.
+    v: &[u64], //any number of values, the proof is padded to a power of 2 internally
     bases: &Bases,
-) -> BulletRangeProof {
+) -> Result<BulletRangeProof, RangeProverError> {
     return bullet_range_proof_ex(gamma, v, bases);
 }
 
-fn bullet_range_proof_ex_T1_T2(
-    gamma: &[Scalar],
-    v: &[u64], //the length of v must be a power of 2 at this point. In case of fake proof, this is a random value
-    bases: &Bases,
-) -> (
+//The blindings of A, SS, T1 and T2 and the masks of the bits. They are random, except in a
+//rewindable proof where they are derived from the rewind key.
+#[allow(non_snake_case)]
+pub(crate) struct RangeProofNonces {
+    alpha: Scalar,
+    rho: Scalar,
+    tao1: Scalar,
+    tao2: Scalar,
+    sL: Vec<Scalar>,
+    sR: Vec<Scalar>,
+}
+
+impl RangeProofNonces {
+    //m is the number of values before padding
+    #[allow(non_snake_case)]
+    pub(crate) fn random(m: usize) -> RangeProofNonces {
+        let n = RANGE_SIZE * to_2s_power(m);
+        let mut csprng: OsRng = OsRng::default();
+        let mut sL = vec![Scalar::default(); n];
+        let mut sR = vec![Scalar::default(); n];
+        fill_random_scalars(&mut sL);
+        fill_random_scalars(&mut sR);
+        RangeProofNonces {
+            alpha: Scalar::random(&mut csprng),
+            rho: Scalar::random(&mut csprng),
+            tao1: Scalar::random(&mut csprng),
+            tao2: Scalar::random(&mut csprng),
+            sL,
+            sR,
+        }
+    }
+
+    //The nonces of a rewindable proof of the single value committed in V
+    #[allow(non_snake_case)]
+    fn from_rewind_key(rewind_key: &Scalar, V: &EdwardsPoint) -> RangeProofNonces {
+        let nonce = |label: &[u8], i: usize| {
+            let mut hasher = Sha512::new();
+            hasher.input(label);
+            hasher.input(rewind_key.as_bytes());
+            hasher.input(&V.compress().to_bytes());
+            hasher.input(&(i as u64).to_le_bytes());
+            Scalar::from_hash(hasher)
+        };
+        RangeProofNonces {
+            alpha: nonce(b"alpha", 0),
+            rho: nonce(b"rho", 0),
+            tao1: nonce(b"tao1", 0),
+            tao2: nonce(b"tao2", 0),
+            sL: (0..RANGE_SIZE).map(|i| nonce(b"sL", i)).collect(),
+            sR: (0..RANGE_SIZE).map(|i| nonce(b"sR", i)).collect(),
+        }
+    }
+}
+
+//T1, T2, l0, r0, l1, r1, tao1, tao2, z, m, alpha, rho, gs, hs, A, SS, y, v, gamma
+pub(crate) type RangeProofCommitment = (
     EdwardsPoint,
     EdwardsPoint,
     Vec<Scalar>,
@@ -606,7 +1033,15 @@ fn bullet_range_proof_ex_T1_T2(
     Scalar,
     Vec<u64>,
     Vec<Scalar>,
-) {
+);
+
+pub(crate) fn bullet_range_proof_ex_T1_T2(
+    gamma: &[Scalar],
+    v: &[u64], //padded to a power of 2 here. In case of fake proof, this is a random value
+    bases: &Bases,
+    nonces: RangeProofNonces,
+) -> Result<RangeProofCommitment, BasesError> {
+    bases.check_capacity(v.len())?;
     let v = to_2s_pow_vec(v);
     let gamma = to_2s_pow_scalar_vec(gamma);
     let m = v.len();
@@ -619,13 +1054,11 @@ fn bullet_range_proof_ex_T1_T2(
 
     let aR = substract_scalar_arrays(&aL, &to_the_n(Scalar::from(1u64), RANGE_SIZE * m));
 
-    let mut csprng: OsRng = OsRng::default();
-    let alpha = Scalar::random(&mut csprng);
-    let mut sL = vec![Scalar::default(); RANGE_SIZE * m];
-    let mut sR = vec![Scalar::default(); RANGE_SIZE * m];
-    fill_random_scalars(&mut sL);
-    fill_random_scalars(&mut sR);
-    let rho = Scalar::random(&mut csprng);
+    assert_eq!(nonces.sL.len(), RANGE_SIZE * m);
+    let alpha = nonces.alpha;
+    let sL = nonces.sL;
+    let sR = nonces.sR;
+    let rho = nonces.rho;
 
     let A = alpha * h + multiscalar_mul_add(&aL, &gs, &aR, &hs);
     let SS = rho * h + multiscalar_mul_add(&sL, &gs, &sR, &hs);
@@ -633,8 +1066,8 @@ fn bullet_range_proof_ex_T1_T2(
     let y = get_hash(&A, &SS);
     let z = get_hash(&SS, &A);
 
-    let tao1 = Scalar::random(&mut csprng);
-    let tao2 = Scalar::random(&mut csprng);
+    let tao1 = nonces.tao1;
+    let tao2 = nonces.tao2;
     let zmn = array_of(z, RANGE_SIZE * m);
     let l0 = substract_scalar_arrays(&aL, &zmn);
     let l1 = sL;
@@ -651,12 +1084,12 @@ fn bullet_range_proof_ex_T1_T2(
     let t2 = inner_product(&l1, &r1);
     let T1 = g * t1 + h * tao1;
     let T2 = g * t2 + h * tao2;
-    return (
+    return Ok((
         T1, T2, l0, r0, l1, r1, tao1, tao2, z, m, alpha, rho, gs, hs, A, SS, y, v, gamma,
-    );
+    ));
 }
 
-fn bullet_range_proof_rest(
+pub(crate) fn bullet_range_proof_rest(
     bases: &Bases,
     T1: EdwardsPoint,
     T2: EdwardsPoint,
@@ -677,6 +1110,7 @@ fn bullet_range_proof_rest(
     y: Scalar,
     v: Vec<u64>,
     gamma: &[Scalar],
+    num_values: usize,
     challenge: Scalar,
 ) -> BulletRangeProof {
     let g = bases.GInit;
@@ -697,9 +1131,11 @@ fn bullet_range_proof_rest(
 
     let mu = alpha + rho * x;
     //assert_eq!(g,h);
+    //only the real values are committed to, the verifier reconstructs the padding
     let V: Vec<EdwardsPoint> = v
         .into_iter()
         .zip(gamma.into_iter())
+        .take(num_values)
         .map(|(x, gam)| Scalar::from(x) * g + gam * h)
         .collect();
 
@@ -722,18 +1158,93 @@ fn bullet_range_proof_rest(
 #[allow(non_snake_case)]
 pub fn bullet_range_proof_ex(
     gamma: &[Scalar],
-    v: &[u64], //the length of v must be a power of 2 at this point. In case of fake proof, this is a random value
+    v: &[u64], //any number of values, the proof is padded to a power of 2 internally
     bases: &Bases,
-) -> BulletRangeProof {
+) -> Result<BulletRangeProof, RangeProverError> {
+    if v.is_empty() {
+        return Err(RangeProverError::NoValues);
+    }
+    if gamma.len() != v.len() {
+        return Err(RangeProverError::LengthMismatch {
+            values: v.len(),
+            blindings: gamma.len(),
+        });
+    }
+    let num_values = v.len();
     let (T1, T2, l0, r0, l1, r1, tao1, tao2, z, m, alpha, rho, gs, hs, A, SS, y, v, gamma) =
-        bullet_range_proof_ex_T1_T2(gamma, v, bases);
+        bullet_range_proof_ex_T1_T2(gamma, v, bases, RangeProofNonces::random(v.len()))
+            .map_err(RangeProverError::Bases)?;
 
     let challenge = get_hash(&T1, &T2);
 
-    return bullet_range_proof_rest(
+    return Ok(bullet_range_proof_rest(
         bases, T1, T2, l0, r0, l1, r1, tao1, tao2, z, m, alpha, rho, gs, hs, A, SS, y, v, &gamma,
-        challenge,
-    );
+        num_values, challenge,
+    ));
+}
+
+pub const REWIND_MEMO_SIZE: usize = 23;
+
+//A proof of the single value v that whoever knows rewind_key can open with
+//bullet_range_rewind. The nonces are derived from the key and V, and v and the memo are
+//added to alpha, which the owner of the key recovers from mu = alpha + rho*x.
+#[allow(non_snake_case)]
+pub fn bullet_range_proof_rewindable(
+    gamma: Scalar,
+    v: u64,
+    memo: &[u8; REWIND_MEMO_SIZE],
+    rewind_key: &Scalar,
+    bases: &Bases,
+) -> Result<BulletRangeProof, BasesError> {
+    let V = Scalar::from(v) * bases.GInit + gamma * bases.HInit;
+    let mut nonces = RangeProofNonces::from_rewind_key(rewind_key, &V);
+    let mut message = [0u8; 32];
+    message[0..8].copy_from_slice(&v.to_le_bytes());
+    message[8..8 + REWIND_MEMO_SIZE].copy_from_slice(memo);
+    nonces.alpha += Scalar::from_bytes_mod_order(message);
+
+    let (T1, T2, l0, r0, l1, r1, tao1, tao2, z, m, alpha, rho, gs, hs, A, SS, y, v, gamma) =
+        bullet_range_proof_ex_T1_T2(&[gamma], &[v], bases, nonces)?;
+    let challenge = get_hash(&T1, &T2);
+    return Ok(bullet_range_proof_rest(
+        bases, T1, T2, l0, r0, l1, r1, tao1, tao2, z, m, alpha, rho, gs, hs, A, SS, y, v, &gamma,
+        1, challenge,
+    ));
+}
+
+//Recovers the value, the blinding and the memo of a proof made by
+//bullet_range_proof_rewindable with the same key. Returns None if the proof was not made
+//with this key. It does not check the proof, bullet_range_verify does.
+#[allow(non_snake_case)]
+pub fn bullet_range_rewind(
+    proof: &BulletRangeProof,
+    bases: &Bases,
+    rewind_key: &Scalar,
+) -> Option<(u64, Scalar, [u8; REWIND_MEMO_SIZE])> {
+    if proof.V.len() != 1 {
+        return None;
+    }
+    let V = proof.V[0];
+    let nonces = RangeProofNonces::from_rewind_key(rewind_key, &V);
+    let x = get_hash(&proof.T1, &proof.T2);
+    let z = get_hash(&proof.SS, &proof.A);
+
+    let message = (proof.mu - nonces.rho * x - nonces.alpha).to_bytes();
+    if message[8 + REWIND_MEMO_SIZE..].iter().any(|b| *b != 0) {
+        return None;
+    }
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&message[0..8]);
+    let v = u64::from_le_bytes(value);
+    let mut memo = [0u8; REWIND_MEMO_SIZE];
+    memo.copy_from_slice(&message[8..8 + REWIND_MEMO_SIZE]);
+
+    //tao_x = x^2*tao2 + x*tao1 + z^2*gamma
+    let gamma = (proof.tao_x - x * x * nonces.tao2 - x * nonces.tao1) * (z * z).invert();
+    if Scalar::from(v) * bases.GInit + gamma * bases.HInit != V {
+        return None;
+    }
+    return Some((v, gamma, memo));
 }
 
 pub fn create_modified_schnorr(
@@ -767,6 +1278,7 @@ pub fn verify_modified_schnorr(
     return hash.eq(&signature.1);
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct RangeOrSchnorrProof {
     range_proof: BulletRangeProof,
     schnorr: (Scalar, Scalar),
@@ -778,27 +1290,27 @@ pub fn create_range_or_schnorr_fake_range(
     bases: &Bases,
     AP_pr_key: Scalar,
     schnorr_message: &[u8],
-) -> RangeOrSchnorrProof {
+) -> Result<RangeOrSchnorrProof, BasesError> {
     let V = v * bases.GInit + gamma * bases.HInit;
     let mut csprng: OsRng = OsRng::default();
     let v_ = csprng.next_u64();
     let challenge = Scalar::random(&mut csprng);
     let (T1_, T2, l0, r0, l1, r1, tao1, tao2, z, m, alpha, rho, gs, hs, A, SS, y, v__, gamma) =
-        bullet_range_proof_ex_T1_T2(&[gamma], &[v_], bases);
+        bullet_range_proof_ex_T1_T2(&[gamma], &[v_], bases, RangeProofNonces::random(1))?;
 
     let v_diff = v - Scalar::from(v_); //v = v_+v_diff;
     let T1 = T1_ - z * z * challenge.invert() * v_diff * bases.GInit;
     let sig = create_modified_schnorr(&bases, schnorr_message, AP_pr_key, (&T1, &T2), challenge);
     let mut bullet_range_proof = bullet_range_proof_rest(
         bases, T1, T2, l0, r0, l1, r1, tao1, tao2, z, m, alpha, rho, gs, hs, A, SS, y, v__, &gamma,
-        challenge,
+        1, challenge,
     );
     bullet_range_proof.V[0] = V;
-    return RangeOrSchnorrProof {
+    return Ok(RangeOrSchnorrProof {
         range_proof: bullet_range_proof,
         schnorr: sig,
         range_hash: challenge,
-    };
+    });
 }
 pub fn create_range_or_schnorr_fake_schnorr(
     gamma: Scalar,
@@ -806,25 +1318,25 @@ pub fn create_range_or_schnorr_fake_schnorr(
     bases: &Bases,
     AP_key: &EdwardsPoint,
     schnorr_message: &[u8],
-) -> RangeOrSchnorrProof {
+) -> Result<RangeOrSchnorrProof, BasesError> {
     let mut csprng: OsRng = OsRng::default();
     let s = Scalar::random(&mut csprng);
     let h = Scalar::random(&mut csprng);
     let G = bases.GInit;
     let R = s * G + h * AP_key;
     let (T1, T2, l0, r0, l1, r1, tao1, tao2, z, m, alpha, rho, gs, hs, A, SS, y, v, gamma) =
-        bullet_range_proof_ex_T1_T2(&[gamma], &[v], bases);
+        bullet_range_proof_ex_T1_T2(&[gamma], &[v], bases, RangeProofNonces::random(1))?;
     let hash = get_hash_of_data_and_points(&schnorr_message, &[&T1, &T2, &AP_key, &R]);
     let challenge = hash - h;
     let bullet_range_proof = bullet_range_proof_rest(
         bases, T1, T2, l0, r0, l1, r1, tao1, tao2, z, m, alpha, rho, gs, hs, A, SS, y, v, &gamma,
-        challenge,
+        1, challenge,
     );
-    return RangeOrSchnorrProof {
+    return Ok(RangeOrSchnorrProof {
         range_proof: bullet_range_proof,
         schnorr: (s, h),
         range_hash: challenge,
-    };
+    });
 }
 
 pub fn verify_range_or_schnorr(
@@ -833,62 +1345,173 @@ pub fn verify_range_or_schnorr(
     AP_key: &EdwardsPoint,
     message: &[u8],
 ) -> bool {
+    return verify_range_or_schnorr_detailed(bases, proof, AP_key, message).is_ok();
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RangeOrSchnorrError {
+    //the challenges of the branches do not add up to the hash
+    ChallengeSplit,
+    Range(RangeProofError),
+    Schnorr,
+}
+
+#[allow(non_snake_case)]
+pub fn verify_range_or_schnorr_detailed(
+    bases: &Bases,
+    proof: &RangeOrSchnorrProof,
+    AP_key: &EdwardsPoint,
+    message: &[u8],
+) -> Result<(), RangeOrSchnorrError> {
     let T1 = proof.range_proof.T1;
     let T2 = proof.range_proof.T2;
     let P = AP_key;
     let R = EdwardsPoint::multiscalar_mul(&[proof.schnorr.0, proof.schnorr.1], &[bases.GInit, *P]);
     let hash = get_hash_of_data_and_points(&message, &[&T1, &T2, &P, &R]);
 
-    hash.eq(&(proof.schnorr.1 + proof.range_hash))
-        && bullet_range_verify_ex(
-            &proof.range_proof,
-            bases,
-            Some((&message, &P, &R)),
-            proof.schnorr.1,
-        )
-        && verify_modified_schnorr(
-            bases,
-            &message,
-            &proof.schnorr,
-            &AP_key,
-            (&proof.range_proof.T1, &proof.range_proof.T2),
-            proof.range_hash,
-        )
+    if !hash.eq(&(proof.schnorr.1 + proof.range_hash)) {
+        return Err(RangeOrSchnorrError::ChallengeSplit);
+    }
+    bullet_range_verify_ex_detailed(
+        &proof.range_proof,
+        bases,
+        Some((&message, &P, &R)),
+        proof.schnorr.1,
+    )
+    .map_err(RangeOrSchnorrError::Range)?;
+    if !verify_modified_schnorr(
+        bases,
+        &message,
+        &proof.schnorr,
+        &AP_key,
+        (&proof.range_proof.T1, &proof.range_proof.T2),
+        proof.range_hash,
+    ) {
+        return Err(RangeOrSchnorrError::Schnorr);
+    }
+    return Ok(());
+}
+
+//Why a range proof of several values could not be created
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RangeProverError {
+    NoValues,
+    //gamma needs one blinding per value
+    LengthMismatch { values: usize, blindings: usize },
+    Bases(BasesError),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BasesError {
+    //a proof of required values (padded to a power of 2) needs more generators than there are
+    InsufficientCapacity { required: usize, available: usize },
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 //The commitment V = v*G+gamma*H
 pub struct Bases {
-    GInit: EdwardsPoint,
-    HInit: EdwardsPoint,
-    Gs: Vec<EdwardsPoint>,
-    Hs: Vec<EdwardsPoint>,
+    pub(crate) GInit: EdwardsPoint,
+    pub(crate) HInit: EdwardsPoint,
+    pub(crate) Gs: Vec<EdwardsPoint>,
+    pub(crate) Hs: Vec<EdwardsPoint>,
+    //where the chain generating Gs and Hs stopped, to extend it
+    next: (EdwardsPoint, EdwardsPoint),
 }
 
 impl Bases {
     pub fn new(GInit: EdwardsPoint, HInit: EdwardsPoint, max_len: usize) -> Bases {
-        let mut ginit = GInit;
-        let mut hinit = HInit;
-        let mut gs = Vec::new();
-        let mut hs = Vec::new();
-        for _ in 0..RANGE_SIZE * max_len {
-            gs.push(get_hash(&ginit, &hinit) * ginit);
-            hs.push(get_hash(&hinit, &ginit) * hinit);
-            ginit = get_hash(&ginit, &hinit) * ginit;
-            hinit = get_hash(&hinit, &ginit) * hinit;
-        }
-        return Bases {
+        let mut bases = Bases {
             GInit: GInit,
             HInit: HInit,
-            Gs: gs,
-            Hs: hs,
+            Gs: Vec::new(),
+            Hs: Vec::new(),
+            next: (GInit, HInit),
         };
+        bases.extend(max_len);
+        return bases;
+    }
+
+    pub fn g(&self) -> EdwardsPoint {
+        return self.GInit;
+    }
+
+    pub fn h(&self) -> EdwardsPoint {
+        return self.HInit;
+    }
+
+    pub fn gs(&self) -> &[EdwardsPoint] {
+        return &self.Gs;
+    }
+
+    pub fn hs(&self) -> &[EdwardsPoint] {
+        return &self.Hs;
+    }
+
+    //The number of values a single proof can hold
+    pub fn capacity(&self) -> usize {
+        return self.num_generators() / RANGE_SIZE;
+    }
+
+    //Gs and Hs have the same length unless the bases were deserialized from a bad source
+    pub(crate) fn num_generators(&self) -> usize {
+        return self.Gs.len().min(self.Hs.len());
+    }
+
+    //Grows the bases to max_len values, the existing generators stay the same so the result
+    //equals Bases::new with max_len.
+    pub fn extend(&mut self, max_len: usize) {
+        let (mut ginit, mut hinit) = self.next;
+        for _ in self.Gs.len()..RANGE_SIZE * max_len {
+            self.Gs.push(get_hash(&ginit, &hinit) * ginit);
+            self.Hs.push(get_hash(&hinit, &ginit) * hinit);
+            ginit = get_hash(&ginit, &hinit) * ginit;
+            hinit = get_hash(&hinit, &ginit) * hinit;
+        }
+        self.next = (ginit, hinit);
+    }
+
+    pub fn check_capacity(&self, m: usize) -> Result<(), BasesError> {
+        let required = to_2s_power(m);
+        if required > self.capacity() {
+            return Err(BasesError::InsufficientCapacity {
+                required,
+                available: self.capacity(),
+            });
+        }
+        return Ok(());
+    }
+
+    //Extends the bases if a proof of m values does not fit
+    pub fn ensure_capacity(&mut self, m: usize) {
+        if self.check_capacity(m).is_err() {
+            self.extend(to_2s_power(m));
+        }
+    }
+
+    //Sha256 of all the generators, parties whose bases have the same digest can exchange
+    //proofs. Compare it with a known value after deserializing bases from an untrusted source.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.input(b"bases");
+        hasher.input(&(self.Gs.len() as u64).to_le_bytes());
+        for point in [self.GInit, self.HInit, self.next.0, self.next.1]
+            .iter()
+            .chain(self.Gs.iter())
+            .chain(self.Hs.iter())
+        {
+            hasher.input(&point.compress().to_bytes());
+        }
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(hasher.result().as_slice());
+        return digest;
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use curve25519_dalek::constants::{BASEPOINT_ORDER, EIGHT_TORSION};
 
     use rand_core::RngCore;
     use rand_core::OsRng;
@@ -904,7 +1527,8 @@ mod tests {
         let AP_key = Scalar::random(&mut csprng) * bases.GInit;
         let message = [0u8, 1u8, 2u8];
 
-        let proof = create_range_or_schnorr_fake_schnorr(gamma, v, &bases, &AP_key, &message);
+        let proof =
+            create_range_or_schnorr_fake_schnorr(gamma, v, &bases, &AP_key, &message).unwrap();
         assert!(verify_range_or_schnorr(&bases, &proof, &AP_key, &message));
     }
 
@@ -920,7 +1544,8 @@ mod tests {
         let AP_key = AP__pr_key * bases.GInit;
         let message = [0u8, 1u8, 2u8];
 
-        let proof = create_range_or_schnorr_fake_range(gamma, v, &bases, AP__pr_key, &message);
+        let proof =
+            create_range_or_schnorr_fake_range(gamma, v, &bases, AP__pr_key, &message).unwrap();
         assert!(verify_range_or_schnorr(&bases, &proof, &AP_key, &message));
     }
 
@@ -945,8 +1570,255 @@ mod tests {
             csprng.next_u64(),
         ];
 
-        let proof = bullet_range_proof(&gamma, &v, &bases);
-        assert!(bullet_range_verify(&proof, bases));
+        let proof = bullet_range_proof(&gamma, &v, &bases).unwrap();
+        assert!(bullet_range_verify(&proof, &bases));
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_bullet_range_proof_non_power_of_two() {
+        let bases = Bases::new(get_L(), get_K(), 8);
+        let mut csprng: OsRng = OsRng::default();
+        for m in [1usize, 3, 6].iter() {
+            let gamma: Vec<Scalar> = (0..*m).map(|_| Scalar::random(&mut csprng)).collect();
+            let v: Vec<u64> = (0..*m).map(|_| csprng.next_u64()).collect();
+
+            let proof = bullet_range_proof(&gamma, &v, &bases).unwrap();
+            assert_eq!(proof.V.len(), *m);
+            assert!(bullet_range_verify(&proof, &bases));
+        }
+    }
+
+    #[test]
+    fn test_bullet_range_proof_bytes() {
+        let bases = Bases::new(get_L(), get_K(), 4);
+        let mut csprng: OsRng = OsRng::default();
+        for m in [1usize, 3, 4].iter() {
+            let gamma: Vec<Scalar> = (0..*m).map(|_| Scalar::random(&mut csprng)).collect();
+            let v: Vec<u64> = (0..*m).map(|_| csprng.next_u64()).collect();
+            let proof = bullet_range_proof(&gamma, &v, &bases).unwrap();
+
+            let bytes = proof.to_bytes();
+            assert_eq!(bytes.len(), BulletRangeProof::serialized_size(*m));
+            assert_eq!(BulletRangeProof::from_bytes(&bytes), Ok(proof));
+            assert_eq!(
+                BulletRangeProof::from_bytes(&bytes[1..]),
+                Err(DecodeError::WrongLength)
+            );
+        }
+
+        let proof = bullet_range_proof(&[Scalar::one()], &[1], &bases).unwrap();
+        //tao_x + l < 2^253 is the same scalar, but not reduced
+        let mut bytes = proof.to_bytes();
+        let mut carry = 0u16;
+        let l = BASEPOINT_ORDER.to_bytes();
+        for i in 0..32 {
+            let sum = bytes[128 + i] as u16 + l[i] as u16 + carry;
+            bytes[128 + i] = sum as u8;
+            carry = sum >> 8;
+        }
+        assert_eq!(
+            BulletRangeProof::from_bytes(&bytes),
+            Err(DecodeError::NonCanonicalScalar)
+        );
+        //A moved out of the prime order subgroup
+        let mut bytes = proof.to_bytes();
+        bytes[0..32].copy_from_slice(&(proof.A + EIGHT_TORSION[1]).compress().to_bytes());
+        assert_eq!(
+            BulletRangeProof::from_bytes(&bytes),
+            Err(DecodeError::InvalidPoint)
+        );
+    }
+
+    #[test]
+    fn test_bullet_range_rewind() {
+        let bases = Bases::new(get_L(), get_K(), 1);
+        let mut csprng: OsRng = OsRng::default();
+        let gamma = Scalar::random(&mut csprng);
+        let v = csprng.next_u64();
+        let key = Scalar::random(&mut csprng);
+        let mut memo = [0u8; REWIND_MEMO_SIZE];
+        memo[0..5].copy_from_slice(b"hello");
+
+        let proof = bullet_range_proof_rewindable(gamma, v, &memo, &key, &bases).unwrap();
+        assert!(bullet_range_verify(&proof, &bases));
+        assert_eq!(
+            bullet_range_rewind(&proof, &bases, &key),
+            Some((v, gamma, memo))
+        );
+        assert_eq!(
+            bullet_range_rewind(&proof, &bases, &Scalar::random(&mut csprng)),
+            None
+        );
+
+        let proof = bullet_range_proof(&[gamma], &[v], &bases).unwrap();
+        assert_eq!(bullet_range_rewind(&proof, &bases, &key), None);
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_bullet_range_proof_rejects_extra_commitments() {
+        let bases = Bases::new(get_L(), get_K(), 4);
+        let mut csprng: OsRng = OsRng::default();
+        let gamma: Vec<Scalar> = (0..3).map(|_| Scalar::random(&mut csprng)).collect();
+        let v: Vec<u64> = (0..3).map(|_| csprng.next_u64()).collect();
+
+        //the padding slot is a commitment to zero, a commitment to one must not fit there
+        let mut proof = bullet_range_proof(&gamma, &v, &bases).unwrap();
+        proof.V.push(bases.GInit);
+        assert!(!bullet_range_verify(&proof, &bases));
+
+        let mut proof = bullet_range_proof(&gamma, &v, &bases).unwrap();
+        proof.V.clear();
+        assert!(!bullet_range_verify(&proof, &bases));
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_bullet_range_verify_batch() {
+        let bases = Bases::new(get_L(), get_K(), 4);
+        let mut csprng: OsRng = OsRng::default();
+        let mut proofs = Vec::new();
+        for m in [1usize, 2, 3, 1].iter() {
+            let gamma: Vec<Scalar> = (0..*m).map(|_| Scalar::random(&mut csprng)).collect();
+            let v: Vec<u64> = (0..*m).map(|_| csprng.next_u64()).collect();
+            proofs.push(bullet_range_proof(&gamma, &v, &bases).unwrap());
+        }
+
+        let refs: Vec<&BulletRangeProof> = proofs.iter().collect();
+        assert_eq!(bullet_range_verify_batch(&refs, &bases), Ok(()));
+        assert_eq!(bullet_range_verify_batch(&[], &bases), Ok(()));
+
+        proofs[2].t_cap += Scalar::one();
+        let refs: Vec<&BulletRangeProof> = proofs.iter().collect();
+        assert_eq!(bullet_range_verify_batch(&refs, &bases), Err(2));
+
+        proofs[2].t_cap -= Scalar::one();
+        proofs[1].bullet_proof.a += Scalar::one();
+        let refs: Vec<&BulletRangeProof> = proofs.iter().collect();
+        assert_eq!(bullet_range_verify_batch(&refs, &bases), Err(1));
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_bullet_range_verify_detailed() {
+        let bases = Bases::new(get_L(), get_K(), 2);
+        let mut csprng: OsRng = OsRng::default();
+        let gamma = [Scalar::random(&mut csprng), Scalar::random(&mut csprng)];
+        let v = [csprng.next_u64(), csprng.next_u64()];
+        let proof = bullet_range_proof(&gamma, &v, &bases).unwrap();
+        assert_eq!(bullet_range_verify_detailed(&proof, &bases), Ok(()));
+
+        let mut bad = proof.clone();
+        bad.tao_x += Scalar::one();
+        assert_eq!(
+            bullet_range_verify_detailed(&bad, &bases),
+            Err(RangeProofError::PolynomialCheck)
+        );
+        let mut bad = proof.clone();
+        bad.mu += Scalar::one();
+        assert_eq!(
+            bullet_range_verify_detailed(&bad, &bases),
+            Err(RangeProofError::InnerProductCheck)
+        );
+        let mut bad = proof.clone();
+        bad.V.clear();
+        assert_eq!(
+            bullet_range_verify_detailed(&bad, &bases),
+            Err(RangeProofError::NoValues)
+        );
+        assert_eq!(
+            bullet_range_verify_detailed(&proof, &Bases::new(get_L(), get_K(), 1)),
+            Err(RangeProofError::Bases(BasesError::InsufficientCapacity {
+                required: 2,
+                available: 1
+            }))
+        );
+
+        //the range branch of an OR proof answers its share of the challenge only
+        let AP_pr_key = Scalar::random(&mut csprng);
+        let AP_key = AP_pr_key * bases.GInit;
+        let mut proof =
+            create_range_or_schnorr_fake_schnorr(gamma[0], v[0], &bases, &AP_key, b"message")
+                .unwrap();
+        assert_eq!(
+            verify_range_or_schnorr_detailed(&bases, &proof, &AP_key, b"message"),
+            Ok(())
+        );
+        assert_eq!(
+            verify_range_or_schnorr_detailed(&bases, &proof, &AP_key, b"other"),
+            Err(RangeOrSchnorrError::ChallengeSplit)
+        );
+        proof.range_proof.bullet_proof.a += Scalar::one();
+        assert_eq!(
+            verify_range_or_schnorr_detailed(&bases, &proof, &AP_key, b"message"),
+            Err(RangeOrSchnorrError::Range(
+                RangeProofError::InnerProductCheck
+            ))
+        );
+    }
+
+    #[test]
+    fn test_bases_capacity() {
+        let mut bases = Bases::new(get_L(), get_K(), 1);
+        assert_eq!(bases.capacity(), 1);
+        assert_eq!(
+            bases.check_capacity(3),
+            Err(BasesError::InsufficientCapacity {
+                required: 4,
+                available: 1
+            })
+        );
+
+        let mut csprng: OsRng = OsRng::default();
+        let gamma: Vec<Scalar> = (0..3).map(|_| Scalar::random(&mut csprng)).collect();
+        let v: Vec<u64> = (0..3).map(|_| csprng.next_u64()).collect();
+        assert_eq!(
+            bullet_range_proof(&gamma, &v, &bases),
+            Err(RangeProverError::Bases(BasesError::InsufficientCapacity {
+                required: 4,
+                available: 1
+            }))
+        );
+        assert_eq!(
+            bullet_range_proof(&gamma[..2], &v, &Bases::new(get_L(), get_K(), 4)),
+            Err(RangeProverError::LengthMismatch {
+                values: 3,
+                blindings: 2
+            })
+        );
+        assert_eq!(
+            bullet_range_proof(&[], &[], &bases),
+            Err(RangeProverError::NoValues)
+        );
+        let proof = bullet_range_proof(&gamma, &v, &Bases::new(get_L(), get_K(), 4)).unwrap();
+        assert!(!bullet_range_verify(&proof, &bases));
+        assert_eq!(
+            bullet_range_verify_detailed(&proof, &bases),
+            Err(RangeProofError::Bases(BasesError::InsufficientCapacity {
+                required: 4,
+                available: 1
+            }))
+        );
+        let refs = [&proof];
+        assert_eq!(bullet_range_verify_batch(&refs, &bases), Err(0));
+
+        bases.ensure_capacity(3);
+        assert_eq!(bases, Bases::new(get_L(), get_K(), 4));
+        assert_eq!(bases.check_capacity(3), Ok(()));
+        assert!(bullet_range_verify(&proof, &bases));
+
+        //Hs cut short, as bases from an untrusted source could be
+        let mut short = bases.clone();
+        short.Hs.truncate(RANGE_SIZE);
+        assert_eq!(short.capacity(), 1);
+        assert!(!bullet_range_verify(&proof, &short));
+        assert_eq!(bullet_range_verify_batch(&refs, &short), Err(0));
+
+        let encoded = bincode::serialize(&bases).unwrap();
+        let decoded: Bases = bincode::deserialize(&encoded).unwrap();
+        assert_eq!(decoded.digest(), bases.digest());
+        assert_ne!(Bases::new(get_L(), get_K(), 1).digest(), bases.digest());
     }
 
     // #[allow(non_snake_case)]
@@ -990,6 +1862,66 @@ mod tests {
         assert!(ver);
     }
 
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_bulletproof_matches_reference() {
+        let bases = Bases::new(get_G(), get_K(), 1);
+        let u = get_L();
+        let mut csprng: OsRng = OsRng::default();
+        let n = RANGE_SIZE;
+        let a: Vec<Scalar> = (0..n).map(|_| Scalar::random(&mut csprng)).collect();
+        let b: Vec<Scalar> = (0..n).map(|_| Scalar::random(&mut csprng)).collect();
+        let hmul: Vec<Scalar> = (0..n).map(|_| Scalar::random(&mut csprng)).collect();
+        let h_ = mult_ed(&hmul, &bases.Hs);
+
+        let P = multiscalar_mul_add(&a, &bases.Gs, &b, &h_) + inner_product(&a, &b) * u;
+        let proof = create_bulletproof(n, &bases.Gs, &h_, u, &a, &b);
+
+        let (_, _, s, s_) = bulletproof_verification_scalars(n, &proof).unwrap();
+        let mut s_reference = vec![Scalar::one(); n];
+        let mut n_ = n;
+        for i in 0..proof.Ls.len() {
+            alt_mult(
+                get_hash(&proof.Ls[i], &proof.Rs[i]).invert(),
+                &mut s_reference,
+                n_ / 2,
+            );
+            n_ = n_ / 2;
+        }
+        assert_eq!(s, s_reference);
+        for i in 0..n {
+            assert_eq!(s[i] * s_[i], Scalar::one());
+        }
+
+        assert!(verify_bulletproof(n, &bases.Gs, &h_, u, P, &proof));
+        assert!(verify_bulletproof_reference(
+            n, &bases.Gs, &h_, u, P, &proof
+        ));
+        assert!(verify_bulletproof_hmul(
+            n, &bases.Gs, &bases.Hs, &hmul, u, P, &proof
+        ));
+        assert!(verify_bulletproof_hmul_reference(
+            n, &bases.Gs, &bases.Hs, &hmul, u, P, &proof
+        ));
+
+        let Q = P + u;
+        assert!(!verify_bulletproof(n, &bases.Gs, &h_, u, Q, &proof));
+        assert!(!verify_bulletproof_reference(
+            n, &bases.Gs, &h_, u, Q, &proof
+        ));
+        assert!(!verify_bulletproof_hmul(
+            n, &bases.Gs, &bases.Hs, &hmul, u, Q, &proof
+        ));
+        assert!(!verify_bulletproof_hmul_reference(
+            n, &bases.Gs, &bases.Hs, &hmul, u, Q, &proof
+        ));
+
+        let mut short_proof = proof.clone();
+        short_proof.Ls.pop();
+        short_proof.Rs.pop();
+        assert!(!verify_bulletproof(n, &bases.Gs, &h_, u, P, &short_proof));
+    }
+
     #[allow(non_snake_case)]
     #[test]
     fn test_modified_schnorr() {
@@ -0,0 +1,559 @@
+//Aggregated range proofs over values owned by different parties. Each party commits to the
+//bits of its own value, the dealer collects the commitments, hands out the challenges and
+//combines the proof shares into a BulletRangeProof accepted by bullet_range_verify.
+//
+//  party                                       dealer
+//  PartyAwaitingPosition::new
+//  assign_position(j)     -- BitCommitment -->  receive_bit_commitments
+//  apply_challenge        <-- BitChallenge ---
+//                         -- PolyCommitment ->  receive_poly_commitments
+//  apply_challenge        <-- PolyChallenge --
+//                         -- ProofShare ----->  receive_shares -> BulletRangeProof
+//
+//The challenges are the same hashes of the summed commitments bullet_range_proof uses, but
+//the dealer can still steer them. It plays the parties that pad the aggregation to a power of
+//2 and can resample their nonces until y, z and x suit it. This does not help a forged proof,
+//the result is checked by bullet_range_verify like any other, and a party rejects the
+//challenges that would reveal its secrets (MaliciousDealer).
+
+use super::bulletproofs::*;
+use alloc::vec::*;
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::IsIdentity;
+use curve25519_dalek::traits::VartimeMultiscalarMul;
+use rand_core::OsRng;
+use serde::*;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MPCError {
+    //the bases are too short for the requested position or number of parties
+    InvalidPosition,
+    WrongNumBitCommitments,
+    WrongNumPolyCommitments,
+    WrongNumProofShares,
+    //the dealer sent a challenge that would reveal the secrets of the party
+    MaliciousDealer,
+    //indexes of the parties whose shares do not match their commitments
+    MalformedProofShares { bad_shares: Vec<usize> },
+}
+
+#[allow(non_snake_case)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BitCommitment {
+    V: EdwardsPoint,
+    A: EdwardsPoint,
+    SS: EdwardsPoint,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BitChallenge {
+    y: Scalar,
+    z: Scalar,
+}
+
+#[allow(non_snake_case)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PolyCommitment {
+    T1: EdwardsPoint,
+    T2: EdwardsPoint,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PolyChallenge {
+    x: Scalar,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProofShare {
+    t_x: Scalar,
+    tao_x: Scalar,
+    mu: Scalar,
+    l: Vec<Scalar>,
+    r: Vec<Scalar>,
+}
+
+fn scalar_pow(x: Scalar, n: usize) -> Scalar {
+    let mut result = Scalar::one();
+    for _ in 0..n {
+        result *= x;
+    }
+    return result;
+}
+
+#[allow(non_snake_case)]
+pub struct PartyAwaitingPosition<'a> {
+    bases: &'a Bases,
+    v: u64,
+    gamma: Scalar,
+    V: EdwardsPoint,
+}
+
+#[allow(non_snake_case)]
+pub struct PartyAwaitingBitChallenge<'a> {
+    bases: &'a Bases,
+    j: usize,
+    gamma: Scalar,
+    aL: Vec<Scalar>,
+    aR: Vec<Scalar>,
+    alpha: Scalar,
+    rho: Scalar,
+    sL: Vec<Scalar>,
+    sR: Vec<Scalar>,
+}
+
+pub struct PartyAwaitingPolyChallenge {
+    z_offset: Scalar,
+    gamma: Scalar,
+    alpha: Scalar,
+    rho: Scalar,
+    l0: Vec<Scalar>,
+    l1: Vec<Scalar>,
+    r0: Vec<Scalar>,
+    r1: Vec<Scalar>,
+    tao1: Scalar,
+    tao2: Scalar,
+}
+
+impl<'a> PartyAwaitingPosition<'a> {
+    #[allow(non_snake_case)]
+    pub fn new(bases: &'a Bases, v: u64, gamma: Scalar) -> PartyAwaitingPosition<'a> {
+        let V = Scalar::from(v) * bases.GInit + gamma * bases.HInit;
+        PartyAwaitingPosition { bases, v, gamma, V }
+    }
+
+    //j is the index of the value of this party in the aggregated proof, it selects the
+    //slice of Gs and Hs the bits are committed under.
+    #[allow(non_snake_case)]
+    pub fn assign_position(
+        self,
+        j: usize,
+    ) -> Result<(PartyAwaitingBitChallenge<'a>, BitCommitment), MPCError> {
+        let bases = self.bases;
+        if bases.num_generators() < (j + 1) * RANGE_SIZE {
+            return Err(MPCError::InvalidPosition);
+        }
+        let gs = &bases.Gs[j * RANGE_SIZE..(j + 1) * RANGE_SIZE];
+        let hs = &bases.Hs[j * RANGE_SIZE..(j + 1) * RANGE_SIZE];
+        let h = bases.HInit;
+
+        let aL = scalars_array_from_bits(self.v).to_vec();
+        let aR = substract_scalar_arrays(&aL, &array_of(Scalar::one(), RANGE_SIZE));
+
+        let mut csprng: OsRng = OsRng::default();
+        let alpha = Scalar::random(&mut csprng);
+        let rho = Scalar::random(&mut csprng);
+        let mut sL = vec![Scalar::zero(); RANGE_SIZE];
+        let mut sR = vec![Scalar::zero(); RANGE_SIZE];
+        fill_random_scalars(&mut sL);
+        fill_random_scalars(&mut sR);
+
+        let A = alpha * h + multiscalar_mul_add(&aL, gs, &aR, hs);
+        let SS = rho * h + multiscalar_mul_add(&sL, gs, &sR, hs);
+
+        return Ok((
+            PartyAwaitingBitChallenge {
+                bases,
+                j,
+                gamma: self.gamma,
+                aL,
+                aR,
+                alpha,
+                rho,
+                sL,
+                sR,
+            },
+            BitCommitment { V: self.V, A, SS },
+        ));
+    }
+}
+
+impl<'a> PartyAwaitingBitChallenge<'a> {
+    #[allow(non_snake_case)]
+    pub fn apply_challenge(
+        self,
+        challenge: &BitChallenge,
+    ) -> (PartyAwaitingPolyChallenge, PolyCommitment) {
+        let y = challenge.y;
+        let z = challenge.z;
+        let g = self.bases.GInit;
+        let h = self.bases.HInit;
+
+        //this party's slice of y^mn and of the z^(2+j)*2^n blocks
+        let ymn =
+            multiply_scalar_to_array(&to_the_n(y, RANGE_SIZE), scalar_pow(y, self.j * RANGE_SIZE));
+        let z_offset = scalar_pow(z, 2 + self.j);
+        let twon_z_pow =
+            multiply_scalar_to_array(&to_the_n(Scalar::from(2u64), RANGE_SIZE), z_offset);
+        let zn = array_of(z, RANGE_SIZE);
+
+        let l0 = substract_scalar_arrays(&self.aL, &zn);
+        let l1 = self.sL;
+        let r0 = add_scalar_arrays(
+            &multiply_scalar_arrays(&ymn, &add_scalar_arrays(&self.aR, &zn)),
+            &twon_z_pow,
+        );
+        let r1 = multiply_scalar_arrays(&ymn, &self.sR);
+
+        let t1 = inner_product(&l0, &r1) + inner_product(&l1, &r0);
+        let t2 = inner_product(&l1, &r1);
+
+        let mut csprng: OsRng = OsRng::default();
+        let tao1 = Scalar::random(&mut csprng);
+        let tao2 = Scalar::random(&mut csprng);
+        let T1 = g * t1 + h * tao1;
+        let T2 = g * t2 + h * tao2;
+
+        return (
+            PartyAwaitingPolyChallenge {
+                z_offset,
+                gamma: self.gamma,
+                alpha: self.alpha,
+                rho: self.rho,
+                l0,
+                l1,
+                r0,
+                r1,
+                tao1,
+                tao2,
+            },
+            PolyCommitment { T1, T2 },
+        );
+    }
+}
+
+impl PartyAwaitingPolyChallenge {
+    pub fn apply_challenge(self, challenge: &PolyChallenge) -> Result<ProofShare, MPCError> {
+        let x = challenge.x;
+        //with x = 0 the share would be l0 = aL - z, which reveals the bits
+        if x == Scalar::zero() {
+            return Err(MPCError::MaliciousDealer);
+        }
+        let l = add_scalar_arrays(&self.l0, &multiply_scalar_to_array(&self.l1, x));
+        let r = add_scalar_arrays(&self.r0, &multiply_scalar_to_array(&self.r1, x));
+        return Ok(ProofShare {
+            t_x: inner_product(&l, &r),
+            tao_x: x * x * self.tao2 + x * self.tao1 + self.z_offset * self.gamma,
+            mu: self.alpha + x * self.rho,
+            l,
+            r,
+        });
+    }
+}
+
+pub struct DealerAwaitingBitCommitments<'a> {
+    bases: &'a Bases,
+    m: usize,
+}
+
+#[allow(non_snake_case)]
+pub struct DealerAwaitingPolyCommitments<'a> {
+    bases: &'a Bases,
+    m: usize,
+    challenge: BitChallenge,
+    bit_commitments: Vec<BitCommitment>,
+    padding: Vec<(PartyAwaitingPolyChallenge, PolyCommitment)>,
+    A_SS: (EdwardsPoint, EdwardsPoint),
+}
+
+#[allow(non_snake_case)]
+pub struct DealerAwaitingProofShares<'a> {
+    bases: &'a Bases,
+    m: usize,
+    bit_challenge: BitChallenge,
+    bit_commitments: Vec<BitCommitment>,
+    A_SS: (EdwardsPoint, EdwardsPoint),
+    poly_challenge: PolyChallenge,
+    poly_commitments: Vec<PolyCommitment>,
+    T1_T2: (EdwardsPoint, EdwardsPoint),
+    padding_shares: Vec<ProofShare>,
+}
+
+impl<'a> DealerAwaitingBitCommitments<'a> {
+    //m is the number of parties, it does not need to be a power of 2
+    pub fn new(bases: &'a Bases, m: usize) -> Result<DealerAwaitingBitCommitments<'a>, MPCError> {
+        if m == 0 || bases.num_generators() < to_2s_power(m) * RANGE_SIZE {
+            return Err(MPCError::InvalidPosition);
+        }
+        return Ok(DealerAwaitingBitCommitments { bases, m });
+    }
+
+    #[allow(non_snake_case)]
+    pub fn receive_bit_commitments(
+        self,
+        bit_commitments: Vec<BitCommitment>,
+    ) -> Result<(DealerAwaitingPolyCommitments<'a>, BitChallenge), MPCError> {
+        if bit_commitments.len() != self.m {
+            return Err(MPCError::WrongNumBitCommitments);
+        }
+
+        //The dealer plays the parties that pad the aggregation to a power of 2. They prove
+        //the value 0 with blinding 0, whose commitment the verifier takes to be the identity.
+        let mut padding_parties = Vec::new();
+        let mut A = EdwardsPoint::default();
+        let mut SS = EdwardsPoint::default();
+        for j in self.m..to_2s_power(self.m) {
+            let (party, commitment) =
+                PartyAwaitingPosition::new(self.bases, 0, Scalar::zero()).assign_position(j)?;
+            A += commitment.A;
+            SS += commitment.SS;
+            padding_parties.push(party);
+        }
+        for commitment in &bit_commitments {
+            A += commitment.A;
+            SS += commitment.SS;
+        }
+
+        let challenge = BitChallenge {
+            y: get_hash(&A, &SS),
+            z: get_hash(&SS, &A),
+        };
+        let padding = padding_parties
+            .into_iter()
+            .map(|party| party.apply_challenge(&challenge))
+            .collect();
+
+        return Ok((
+            DealerAwaitingPolyCommitments {
+                bases: self.bases,
+                m: self.m,
+                challenge,
+                bit_commitments,
+                padding,
+                A_SS: (A, SS),
+            },
+            challenge,
+        ));
+    }
+}
+
+impl<'a> DealerAwaitingPolyCommitments<'a> {
+    #[allow(non_snake_case)]
+    pub fn receive_poly_commitments(
+        self,
+        poly_commitments: Vec<PolyCommitment>,
+    ) -> Result<(DealerAwaitingProofShares<'a>, PolyChallenge), MPCError> {
+        if poly_commitments.len() != self.m {
+            return Err(MPCError::WrongNumPolyCommitments);
+        }
+
+        let mut T1 = EdwardsPoint::default();
+        let mut T2 = EdwardsPoint::default();
+        for commitment in poly_commitments
+            .iter()
+            .chain(self.padding.iter().map(|(_, commitment)| commitment))
+        {
+            T1 += commitment.T1;
+            T2 += commitment.T2;
+        }
+
+        let challenge = PolyChallenge {
+            x: get_hash(&T1, &T2),
+        };
+        let mut padding_shares = Vec::new();
+        for (party, _) in self.padding {
+            padding_shares.push(party.apply_challenge(&challenge)?);
+        }
+
+        return Ok((
+            DealerAwaitingProofShares {
+                bases: self.bases,
+                m: self.m,
+                bit_challenge: self.challenge,
+                bit_commitments: self.bit_commitments,
+                A_SS: self.A_SS,
+                poly_challenge: challenge,
+                poly_commitments,
+                T1_T2: (T1, T2),
+                padding_shares,
+            },
+            challenge,
+        ));
+    }
+}
+
+impl<'a> DealerAwaitingProofShares<'a> {
+    //Checks the share of party j against its own commitments, the same two equations
+    //bullet_range_verify_ex checks for the whole proof restricted to the slice of party j.
+    #[allow(non_snake_case)]
+    fn audit_share(&self, j: usize, share: &ProofShare) -> bool {
+        if share.l.len() != RANGE_SIZE || share.r.len() != RANGE_SIZE {
+            return false;
+        }
+        if inner_product(&share.l, &share.r) != share.t_x {
+            return false;
+        }
+        let bases = self.bases;
+        let y = self.bit_challenge.y;
+        let z = self.bit_challenge.z;
+        let x = self.poly_challenge.x;
+        let bit_commitment = &self.bit_commitments[j];
+        let poly_commitment = &self.poly_commitments[j];
+
+        let y_offset = scalar_pow(y, j * RANGE_SIZE);
+        let z_offset = scalar_pow(z, 2 + j);
+        let ymn = multiply_scalar_to_array(&to_the_n(y, RANGE_SIZE), y_offset);
+        let y_mn = multiply_scalar_to_array(&to_the_n(y.invert(), RANGE_SIZE), y_offset.invert());
+        let twon = to_the_n(Scalar::from(2u64), RANGE_SIZE);
+
+        //t_x*g + tao_x*h = z^(2+j)*V + delta_j*g + x*T1 + x^2*T2
+        let delta = (z - z * z) * ymn.iter().fold(Scalar::zero(), |acc, y| acc + y)
+            - z * z_offset * twon.iter().fold(Scalar::zero(), |acc, t| acc + t);
+        let check = EdwardsPoint::vartime_multiscalar_mul(
+            &[share.t_x - delta, share.tao_x, -z_offset, -x, -x * x],
+            &[
+                bases.GInit,
+                bases.HInit,
+                bit_commitment.V,
+                poly_commitment.T1,
+                poly_commitment.T2,
+            ],
+        );
+        if !check.is_identity() {
+            return false;
+        }
+
+        //<l,G_j> + <r*y^-n,H_j> + mu*h = A + x*SS - z*<1,G_j> + <z + z^(2+j)*2^n*y^-n,H_j>
+        let gs = &bases.Gs[j * RANGE_SIZE..(j + 1) * RANGE_SIZE];
+        let hs = &bases.Hs[j * RANGE_SIZE..(j + 1) * RANGE_SIZE];
+        let scalars = share
+            .l
+            .iter()
+            .map(|l| l + z)
+            .chain((0..RANGE_SIZE).map(|i| y_mn[i] * (share.r[i] - z_offset * twon[i]) - z))
+            .chain(vec![share.mu, -Scalar::one(), -x]);
+        let points = gs
+            .iter()
+            .chain(hs.iter())
+            .chain(vec![bases.HInit, bit_commitment.A, bit_commitment.SS].iter())
+            .map(|p| *p)
+            .collect::<Vec<EdwardsPoint>>();
+        return EdwardsPoint::vartime_multiscalar_mul(scalars, points).is_identity();
+    }
+
+    #[allow(non_snake_case)]
+    pub fn receive_shares(self, shares: &[ProofShare]) -> Result<BulletRangeProof, MPCError> {
+        if shares.len() != self.m {
+            return Err(MPCError::WrongNumProofShares);
+        }
+        let bad_shares: Vec<usize> = (0..self.m)
+            .filter(|j| !self.audit_share(*j, &shares[*j]))
+            .collect();
+        if bad_shares.len() > 0 {
+            return Err(MPCError::MalformedProofShares { bad_shares });
+        }
+
+        let mut t_cap = Scalar::zero();
+        let mut tao_x = Scalar::zero();
+        let mut mu = Scalar::zero();
+        let mut l = Vec::new();
+        let mut r = Vec::new();
+        for share in shares.iter().chain(self.padding_shares.iter()) {
+            t_cap += share.t_x;
+            tao_x += share.tao_x;
+            mu += share.mu;
+            l.extend_from_slice(&share.l);
+            r.extend_from_slice(&share.r);
+        }
+
+        let n = l.len();
+        let gs = &self.bases.Gs[0..n];
+        let hs = &self.bases.Hs[0..n];
+        let h_ = mult_ed(&to_the_n(self.bit_challenge.y.invert(), n), hs);
+        let bullet_proof = create_bulletproof(n, gs, &h_, self.bases.HInit, &l, &r);
+
+        return Ok(BulletRangeProof {
+            A: self.A_SS.0,
+            SS: self.A_SS.1,
+            T1: self.T1_T2.0,
+            T2: self.T1_T2.1,
+            tao_x,
+            mu,
+            t_cap,
+            V: self.bit_commitments.iter().map(|c| c.V).collect(),
+            bullet_proof,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::*;
+    use rand_core::RngCore;
+
+    fn run_protocol(
+        bases: &Bases,
+        v: &[u64],
+        gamma: &[Scalar],
+        tamper_with: Option<usize>,
+    ) -> Result<BulletRangeProof, MPCError> {
+        let m = v.len();
+        let dealer = DealerAwaitingBitCommitments::new(bases, m)?;
+
+        let mut parties = Vec::new();
+        let mut bit_commitments = Vec::new();
+        for j in 0..m {
+            let (party, commitment) =
+                PartyAwaitingPosition::new(bases, v[j], gamma[j]).assign_position(j)?;
+            parties.push(party);
+            bit_commitments.push(commitment);
+        }
+        let (dealer, bit_challenge) = dealer.receive_bit_commitments(bit_commitments)?;
+
+        let mut parties_ = Vec::new();
+        let mut poly_commitments = Vec::new();
+        for party in parties {
+            let (party, commitment) = party.apply_challenge(&bit_challenge);
+            parties_.push(party);
+            poly_commitments.push(commitment);
+        }
+        let (dealer, poly_challenge) = dealer.receive_poly_commitments(poly_commitments)?;
+
+        let mut shares = Vec::new();
+        for party in parties_ {
+            shares.push(party.apply_challenge(&poly_challenge)?);
+        }
+        if let Some(j) = tamper_with {
+            shares[j].mu += Scalar::one();
+        }
+        return dealer.receive_shares(&shares);
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_mpc_range_proof() {
+        let bases = Bases::new(get_L(), get_K(), 4);
+        let mut csprng: OsRng = OsRng::default();
+        for m in [1usize, 2, 3].iter() {
+            let v: Vec<u64> = (0..*m).map(|_| csprng.next_u64()).collect();
+            let gamma: Vec<Scalar> = (0..*m).map(|_| Scalar::random(&mut csprng)).collect();
+
+            let proof = run_protocol(&bases, &v, &gamma, None).unwrap();
+            assert_eq!(proof.V.len(), *m);
+            for j in 0..*m {
+                assert_eq!(
+                    proof.V[j],
+                    Scalar::from(v[j]) * get_L() + gamma[j] * get_K()
+                );
+            }
+            assert!(bullet_range_verify(&proof, &bases));
+        }
+    }
+
+    #[test]
+    fn test_mpc_bad_share() {
+        let bases = Bases::new(get_L(), get_K(), 4);
+        let mut csprng: OsRng = OsRng::default();
+        let v: Vec<u64> = (0..3).map(|_| csprng.next_u64()).collect();
+        let gamma: Vec<Scalar> = (0..3).map(|_| Scalar::random(&mut csprng)).collect();
+
+        assert_eq!(
+            run_protocol(&bases, &v, &gamma, Some(1)),
+            Err(MPCError::MalformedProofShares {
+                bad_shares: vec![1]
+            })
+        );
+        assert!(DealerAwaitingBitCommitments::new(&bases, 5).is_err());
+    }
+}
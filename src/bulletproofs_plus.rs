@@ -0,0 +1,367 @@
+//Bulletproofs+ range proofs (Chung, Han, Ju, Kim, Seo 2020) over the same Bases and commitments
+//V = v*GInit + gamma*HInit as BulletRangeProof. The polynomial commitments T1, T2 and the
+//scalars tao_x, mu, t_cap are replaced by a weighted inner product argument, which saves a
+//point and two scalars per proof and makes the verification a single multiscalar
+//multiplication.
+//
+//Like bullet_range_proof the values are padded to a power of 2 with commitments to zero made
+//with a zero blinding, which the verifier takes to be the identity, so V only holds the real
+//commitments.
+
+use super::bulletproofs::*;
+use alloc::vec::*;
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::IsIdentity;
+use curve25519_dalek::traits::VartimeMultiscalarMul;
+use rand_core::OsRng;
+use serde::*;
+
+//Proves P = <a,G> + <b,H> + (a (.)_y b)*g + alpha*h where a (.)_y b = sum(y^(i+1)*a_i*b_i).
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WeightedInnerProductProof {
+    Ls: Vec<EdwardsPoint>,
+    Rs: Vec<EdwardsPoint>,
+    A: EdwardsPoint,
+    B: EdwardsPoint,
+    r_: Scalar,
+    s_: Scalar,
+    delta_: Scalar,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BulletPlusRangeProof {
+    pub V: Vec<EdwardsPoint>,
+    A: EdwardsPoint,
+    wip: WeightedInnerProductProof,
+}
+
+fn weighted_inner_product(a: &[Scalar], b: &[Scalar], y: Scalar) -> Scalar {
+    let mut sum = Scalar::zero();
+    let mut y_i = y;
+    for i in 0..a.len() {
+        sum += y_i * a[i] * b[i];
+        y_i *= y;
+    }
+    return sum;
+}
+
+#[allow(non_snake_case)]
+fn create_weighted_inner_product_proof(
+    transcript: &mut Transcript,
+    g: EdwardsPoint,
+    h: EdwardsPoint,
+    gs: &[EdwardsPoint],
+    hs: &[EdwardsPoint],
+    y: Scalar,
+    a: Vec<Scalar>,
+    b: Vec<Scalar>,
+    alpha: Scalar,
+) -> WeightedInnerProductProof {
+    let mut csprng: OsRng = OsRng::default();
+    let mut G = gs.to_vec();
+    let mut H = hs.to_vec();
+    let mut a = a;
+    let mut b = b;
+    let mut alpha = alpha;
+    let mut Ls = Vec::new();
+    let mut Rs = Vec::new();
+    let mut n = a.len();
+    while n > 1 {
+        let n_ = n / 2;
+        let y_n = to_the_n(y, n_ + 1)[n_];
+        let y_n_inv = y_n.invert();
+        let (a1, a2) = a.split_at(n_);
+        let (b1, b2) = b.split_at(n_);
+        let (G1, G2) = G.split_at(n_);
+        let (H1, H2) = H.split_at(n_);
+
+        let cL = weighted_inner_product(a1, b2, y);
+        let cR = y_n * weighted_inner_product(a2, b1, y);
+        let dL = Scalar::random(&mut csprng);
+        let dR = Scalar::random(&mut csprng);
+        let L = multiscalar_mul_add(&multiply_scalar_to_array(a1, y_n_inv), G2, b2, H1)
+            + cL * g
+            + dL * h;
+        let R =
+            multiscalar_mul_add(&multiply_scalar_to_array(a2, y_n), G1, b1, H2) + cR * g + dR * h;
+        transcript.append_point(&L);
+        transcript.append_point(&R);
+        let e = transcript.challenge();
+        let e_inv = e.invert();
+
+        let G_ = add_mult_ed(e_inv, G1, e * y_n_inv, G2);
+        let H_ = add_mult_ed(e, H1, e_inv, H2);
+        let a_ = add_mult(e, a1, y_n * e_inv, a2);
+        let b_ = add_mult(e_inv, b1, e, b2);
+        alpha += dL * e * e + dR * e_inv * e_inv;
+        G = G_;
+        H = H_;
+        a = a_;
+        b = b_;
+        Ls.push(L);
+        Rs.push(R);
+        n = n_;
+    }
+
+    let r = Scalar::random(&mut csprng);
+    let s = Scalar::random(&mut csprng);
+    let delta = Scalar::random(&mut csprng);
+    let eta = Scalar::random(&mut csprng);
+    let A = r * G[0] + s * H[0] + (r * y * b[0] + s * y * a[0]) * g + delta * h;
+    let B = (r * y * s) * g + eta * h;
+    transcript.append_point(&A);
+    transcript.append_point(&B);
+    let e = transcript.challenge();
+
+    return WeightedInnerProductProof {
+        Ls,
+        Rs,
+        A,
+        B,
+        r_: r + a[0] * e,
+        s_: s + b[0] * e,
+        delta_: eta + delta * e + alpha * e * e,
+    };
+}
+
+//d_i*y^(mn-i) where d is z^(2+2j)*2^n in the block of the j-th value, i counting from 0
+fn range_proof_d_y(y: Scalar, z: Scalar, m: usize) -> Vec<Scalar> {
+    let n = RANGE_SIZE * m;
+    let mut y_rev = to_the_n(y, n + 1);
+    y_rev.reverse();
+    let twon = to_the_n(Scalar::from(2u64), RANGE_SIZE);
+    let mut d_y = Vec::with_capacity(n);
+    let mut z_2j = z * z;
+    for j in 0..m {
+        for i in 0..RANGE_SIZE {
+            d_y.push(z_2j * twon[i] * y_rev[j * RANGE_SIZE + i]);
+        }
+        z_2j *= z * z;
+    }
+    return d_y;
+}
+
+#[allow(non_snake_case)]
+fn range_proof_transcript(V: &[EdwardsPoint], A: &EdwardsPoint) -> (Transcript, Scalar, Scalar) {
+    let mut transcript = Transcript::new(b"bulletproofs+ range proof");
+    transcript.append_u64(V.len() as u64);
+    for V_j in V {
+        transcript.append_point(V_j);
+    }
+    transcript.append_point(A);
+    let y = transcript.challenge();
+    let z = transcript.challenge();
+    return (transcript, y, z);
+}
+
+#[allow(non_snake_case)]
+pub fn bullet_plus_range_proof(
+    gamma: &[Scalar],
+    v: &[u64], //any number of values, the proof is padded to a power of 2 internally
+    bases: &Bases,
+) -> Result<BulletPlusRangeProof, RangeProverError> {
+    if v.is_empty() {
+        return Err(RangeProverError::NoValues);
+    }
+    if gamma.len() != v.len() {
+        return Err(RangeProverError::LengthMismatch {
+            values: v.len(),
+            blindings: gamma.len(),
+        });
+    }
+    bases
+        .check_capacity(v.len())
+        .map_err(RangeProverError::Bases)?;
+    let m = to_2s_power(v.len());
+    let n = RANGE_SIZE * m;
+    let gs = &bases.Gs[0..n];
+    let hs = &bases.Hs[0..n];
+    let g = bases.GInit;
+    let h = bases.HInit;
+
+    let V: Vec<EdwardsPoint> = (0..v.len())
+        .map(|j| Scalar::from(v[j]) * g + gamma[j] * h)
+        .collect();
+
+    let mut aL = Vec::with_capacity(n);
+    for j in 0..m {
+        let v_j = if j < v.len() { v[j] } else { 0 };
+        aL.extend_from_slice(&scalars_array_from_bits(v_j));
+    }
+    let aR = substract_scalar_arrays(&aL, &array_of(Scalar::one(), n));
+    let mut csprng: OsRng = OsRng::default();
+    let alpha = Scalar::random(&mut csprng);
+    let A = alpha * h + multiscalar_mul_add(&aL, gs, &aR, hs);
+
+    let (mut transcript, y, z) = range_proof_transcript(&V, &A);
+
+    let zn = array_of(z, n);
+    let a = substract_scalar_arrays(&aL, &zn);
+    let b = add_scalar_arrays(&add_scalar_arrays(&aR, &range_proof_d_y(y, z, m)), &zn);
+    let y_n1 = to_the_n(y, n + 2)[n + 1];
+    let mut alpha_ = alpha;
+    let mut z_2j = z * z;
+    for j in 0..v.len() {
+        alpha_ += z_2j * y_n1 * gamma[j];
+        z_2j *= z * z;
+    }
+
+    let wip = create_weighted_inner_product_proof(&mut transcript, g, h, gs, hs, y, a, b, alpha_);
+    return Ok(BulletPlusRangeProof { V, A, wip });
+}
+
+//<1,d> = (2^n - 1)*sum(z^(2+2j))
+fn d_sum(z: Scalar, m: usize) -> Scalar {
+    let mut sum = Scalar::zero();
+    let mut z_2j = z * z;
+    for _ in 0..m {
+        sum += z_2j;
+        z_2j *= z * z;
+    }
+    let twon = to_the_n(Scalar::from(2u64), RANGE_SIZE);
+    return sum * twon.iter().fold(Scalar::zero(), |acc, t| acc + t);
+}
+
+#[allow(non_snake_case)]
+pub fn bullet_plus_range_verify(proof: &BulletPlusRangeProof, bases: &Bases) -> bool {
+    let num_values = proof.V.len();
+    if num_values == 0 {
+        return false;
+    }
+    let m = to_2s_power(num_values);
+    let n = RANGE_SIZE * m;
+    let wip = &proof.wip;
+    let rounds = wip.Ls.len();
+    if wip.Rs.len() != rounds
+        || rounds >= 64
+        || n != 1 << rounds
+        || bases.check_capacity(num_values).is_err()
+    {
+        return false;
+    }
+
+    let (mut transcript, y, z) = range_proof_transcript(&proof.V, &proof.A);
+    let mut challenges = Vec::with_capacity(rounds);
+    for j in 0..rounds {
+        transcript.append_point(&wip.Ls[j]);
+        transcript.append_point(&wip.Rs[j]);
+        challenges.push(transcript.challenge());
+    }
+    transcript.append_point(&wip.A);
+    transcript.append_point(&wip.B);
+    let e = transcript.challenge();
+    let (x_sq, x_inv_sq, s, s_) = folding_scalars(n, challenges);
+
+    //The range statement turns A into A_ = A - z*<1,G> + <d*y_rev + z,H> + zeta*g +
+    //sum(z^(2+2j)*y^(mn+1)*V_j), and the weighted inner product argument checks
+    //e^2*(A_ + sum(x^2*L + x^-2*R)) + e*A' + B = e*r'*<s*y^-i,G> + e*s'*<s^-1,H> + r'*y*s'*g + delta'*h
+    let e_sq = e * e;
+    let y_n1 = to_the_n(y, n + 2)[n + 1];
+    let y_inv = to_the_n(y.invert(), n);
+    let d_y = range_proof_d_y(y, z, m);
+    let sum_y = to_the_n(y, n + 1)[1..]
+        .iter()
+        .fold(Scalar::zero(), |acc, y_i| acc + y_i);
+    let sum_d = d_sum(z, m);
+    let zeta = (z - z * z) * sum_y - z * y_n1 * sum_d;
+
+    let r_e = wip.r_ * e;
+    let s_e = wip.s_ * e;
+    let mut z_2j = z * z;
+    let mut v_scalars = Vec::with_capacity(num_values);
+    for _ in 0..num_values {
+        v_scalars.push(e_sq * z_2j * y_n1);
+        z_2j *= z * z;
+    }
+
+    let scalars = (0..n)
+        .map(|i| -e_sq * z - r_e * s[i] * y_inv[i])
+        .chain((0..n).map(|i| e_sq * (d_y[i] + z) - s_e * s_[i]))
+        .chain(vec![
+            e_sq * zeta - wip.r_ * y * wip.s_,
+            -wip.delta_,
+            e_sq,
+            e,
+            Scalar::one(),
+        ])
+        .chain(v_scalars)
+        .chain(x_sq.iter().map(|x| e_sq * x))
+        .chain(x_inv_sq.iter().map(|x| e_sq * x));
+    let points = bases.Gs[0..n]
+        .iter()
+        .chain(bases.Hs[0..n].iter())
+        .chain(vec![bases.GInit, bases.HInit, proof.A, wip.A, wip.B].iter())
+        .chain(proof.V.iter())
+        .chain(wip.Ls.iter())
+        .chain(wip.Rs.iter())
+        .map(|p| *p)
+        .collect::<Vec<EdwardsPoint>>();
+    return EdwardsPoint::vartime_multiscalar_mul(scalars, points).is_identity();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::*;
+    use rand_core::RngCore;
+
+    #[test]
+    fn test_bullet_plus_range_proof() {
+        let bases = Bases::new(get_L(), get_K(), 4);
+        let mut csprng: OsRng = OsRng::default();
+        for m in [1usize, 2, 3].iter() {
+            let gamma: Vec<Scalar> = (0..*m).map(|_| Scalar::random(&mut csprng)).collect();
+            let v: Vec<u64> = (0..*m).map(|_| csprng.next_u64()).collect();
+
+            let mut proof = bullet_plus_range_proof(&gamma, &v, &bases).unwrap();
+            assert_eq!(proof.V.len(), *m);
+            assert!(bullet_plus_range_verify(&proof, &bases));
+
+            proof.V[0] += bases.GInit;
+            assert!(!bullet_plus_range_verify(&proof, &bases));
+            proof.V[0] -= bases.GInit;
+            proof.V.push(bases.GInit);
+            assert!(!bullet_plus_range_verify(&proof, &bases));
+        }
+
+        let short = Bases::new(get_L(), get_K(), 1);
+        let gamma = [Scalar::one(), Scalar::one()];
+        assert_eq!(
+            bullet_plus_range_proof(&gamma, &[1, 2], &short).err(),
+            Some(RangeProverError::Bases(BasesError::InsufficientCapacity {
+                required: 2,
+                available: 1
+            }))
+        );
+        assert_eq!(
+            bullet_plus_range_proof(&gamma, &[1, 2, 3], &bases).err(),
+            Some(RangeProverError::LengthMismatch {
+                values: 3,
+                blindings: 2
+            })
+        );
+        assert_eq!(
+            bullet_plus_range_proof(&[], &[], &bases).err(),
+            Some(RangeProverError::NoValues)
+        );
+        let proof = bullet_plus_range_proof(&gamma, &[1, 2], &bases).unwrap();
+        assert!(!bullet_plus_range_verify(&proof, &short));
+    }
+
+    #[test]
+    fn test_bullet_plus_range_proof_is_smaller() {
+        let bases = Bases::new(get_L(), get_K(), 2);
+        let mut csprng: OsRng = OsRng::default();
+        let gamma: Vec<Scalar> = (0..2).map(|_| Scalar::random(&mut csprng)).collect();
+        let v: Vec<u64> = (0..2).map(|_| csprng.next_u64()).collect();
+
+        let plus =
+            bincode::serialize(&bullet_plus_range_proof(&gamma, &v, &bases).unwrap()).unwrap();
+        let classic = bincode::serialize(&bullet_range_proof(&gamma, &v, &bases).unwrap()).unwrap();
+        //A, SS, T1, T2, tao_x, mu, t_cap, a, b against A, A', B, r', s', delta'
+        assert_eq!(classic.len() - plus.len(), 3 * 32);
+    }
+}
@@ -0,0 +1,127 @@
+//Proofs that committed values compare, for auctions and spending caps. v1 >= v2 holds when
+//v1 - v2 is in [0, 2^64), which is a range proof on V1 - V2 = (v1 - v2)*GInit +
+//(gamma1 - gamma2)*HInit. Against a public cap the range proof is on cap*GInit - V.
+//
+//The verifiers check the commitment of the range proof is the difference itself, so a proof
+//made for other commitments is rejected.
+
+use super::bulletproofs::*;
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::scalar::Scalar;
+use serde::*;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ComparisonProof {
+    range_proof: BulletRangeProof,
+}
+
+fn verify_difference(bases: &Bases, difference: &EdwardsPoint, proof: &ComparisonProof) -> bool {
+    if proof.range_proof.V.len() != 1 || proof.range_proof.V[0] != *difference {
+        return false;
+    }
+    return bullet_range_verify(&proof.range_proof, bases);
+}
+
+//None if v1 < v2 or the bases cannot hold a value
+pub fn prove_geq(
+    bases: &Bases,
+    v1: u64,
+    gamma1: Scalar,
+    v2: u64,
+    gamma2: Scalar,
+) -> Option<ComparisonProof> {
+    if v1 < v2 {
+        return None;
+    }
+    return Some(ComparisonProof {
+        range_proof: bullet_range_proof(&[gamma1 - gamma2], &[v1 - v2], bases).ok()?,
+    });
+}
+
+#[allow(non_snake_case)]
+pub fn verify_geq(
+    bases: &Bases,
+    V1: &EdwardsPoint,
+    V2: &EdwardsPoint,
+    proof: &ComparisonProof,
+) -> bool {
+    return verify_difference(bases, &(V1 - V2), proof);
+}
+
+//v <= cap for a public cap, None if v > cap or the bases cannot hold a value
+pub fn prove_leq_public(bases: &Bases, v: u64, gamma: Scalar, cap: u64) -> Option<ComparisonProof> {
+    if v > cap {
+        return None;
+    }
+    return Some(ComparisonProof {
+        range_proof: bullet_range_proof(&[-gamma], &[cap - v], bases).ok()?,
+    });
+}
+
+#[allow(non_snake_case)]
+pub fn verify_leq_public(
+    bases: &Bases,
+    V: &EdwardsPoint,
+    cap: u64,
+    proof: &ComparisonProof,
+) -> bool {
+    return verify_difference(bases, &(Scalar::from(cap) * bases.GInit - V), proof);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::core::*;
+    use super::*;
+    use rand_core::OsRng;
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_geq() {
+        let bases = Bases::new(get_L(), get_K(), 1);
+        let mut csprng: OsRng = OsRng::default();
+        let gamma1 = Scalar::random(&mut csprng);
+        let gamma2 = Scalar::random(&mut csprng);
+        let V = |v: u64, gamma: Scalar| Scalar::from(v) * bases.GInit + gamma * bases.HInit;
+
+        let proof = prove_geq(&bases, 1000, gamma1, 999, gamma2).unwrap();
+        assert!(verify_geq(
+            &bases,
+            &V(1000, gamma1),
+            &V(999, gamma2),
+            &proof
+        ));
+        assert!(!verify_geq(
+            &bases,
+            &V(999, gamma2),
+            &V(1000, gamma1),
+            &proof
+        ));
+        assert!(!verify_geq(
+            &bases,
+            &V(1000, gamma1),
+            &V(998, gamma2),
+            &proof
+        ));
+
+        let proof = prove_geq(&bases, 7, gamma1, 7, gamma2).unwrap();
+        assert!(verify_geq(&bases, &V(7, gamma1), &V(7, gamma2), &proof));
+        assert!(prove_geq(&bases, 6, gamma1, 7, gamma2).is_none());
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_leq_public() {
+        let bases = Bases::new(get_L(), get_K(), 1);
+        let mut csprng: OsRng = OsRng::default();
+        let gamma = Scalar::random(&mut csprng);
+        let V = Scalar::from(500u64) * bases.GInit + gamma * bases.HInit;
+
+        let proof = prove_leq_public(&bases, 500, gamma, 800).unwrap();
+        assert!(verify_leq_public(&bases, &V, 800, &proof));
+        assert!(!verify_leq_public(&bases, &V, 799, &proof));
+        assert!(prove_leq_public(&bases, 500, gamma, 499).is_none());
+
+        let proof = prove_leq_public(&bases, 500, gamma, 500).unwrap();
+        assert!(verify_leq_public(&bases, &V, 500, &proof));
+    }
+}
@@ -0,0 +1,190 @@
+//Baby step giant step table to recover small values v from v*G, built once and queried for any
+//number of points. The table holds the first 8 bytes of j*G for j < 2^table_bits sorted, so a
+//lookup costs 2^(max_bits - table_bits) point subtractions and binary searches at most.
+//A prefix match is confirmed by recomputing j*G, so a corrupted table can miss a value but
+//never returns a wrong one.
+
+use super::core::*;
+use alloc::vec::*;
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::scalar::Scalar;
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiscreteLogTable {
+    G: EdwardsPoint,
+    table_bits: u32,
+    max_bits: u32,
+    //(prefix of j*G, j) sorted by prefix
+    entries: Vec<(u64, u32)>,
+}
+
+fn prefix(point: &EdwardsPoint) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&point.compress().as_bytes()[0..8]);
+    return u64::from_le_bytes(bytes);
+}
+
+impl DiscreteLogTable {
+    //Finds the values in [0, 2^max_bits) with a table of 2^table_bits entries, table_bits at
+    //most 32 and max_bits at most 64.
+    #[allow(non_snake_case)]
+    pub fn new(G: EdwardsPoint, table_bits: u32, max_bits: u32) -> DiscreteLogTable {
+        assert!(table_bits <= 32 && max_bits <= 64 && table_bits <= max_bits);
+        let mut entries = Vec::with_capacity(1usize << table_bits);
+        let mut V = EdwardsPoint::default();
+        for j in 0..(1u64 << table_bits) {
+            entries.push((prefix(&V), j as u32));
+            V += G;
+        }
+        entries.sort();
+        DiscreteLogTable {
+            G,
+            table_bits,
+            max_bits,
+            entries,
+        }
+    }
+
+    pub fn table_bits(&self) -> u32 {
+        return self.table_bits;
+    }
+
+    pub fn max_bits(&self) -> u32 {
+        return self.max_bits;
+    }
+
+    //Returns v such that v*G = H if there is one in [0, 2^max_bits).
+    #[allow(non_snake_case)]
+    pub fn get(&self, H: &EdwardsPoint) -> Option<u64> {
+        let giant_step = Scalar::from(1u64 << self.table_bits) * self.G;
+        let giant_steps = 1u128 << (self.max_bits - self.table_bits);
+        let mut S = *H;
+        let mut i = 0u128;
+        while i < giant_steps {
+            let key = prefix(&S);
+            let start = self.entries.partition_point(|(p, _)| *p < key);
+            for (p, j) in &self.entries[start..] {
+                if *p != key {
+                    break;
+                }
+                if Scalar::from(*j as u64) * self.G == S {
+                    return Some(((i as u64) << self.table_bits) + *j as u64);
+                }
+            }
+            S -= giant_step;
+            i += 1;
+        }
+        return None;
+    }
+
+    //G, table_bits, max_bits and the entries, 12 bytes each
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(40 + 12 * self.entries.len());
+        bytes.extend_from_slice(&self.G.compress().to_bytes());
+        bytes.extend_from_slice(&self.table_bits.to_le_bytes());
+        bytes.extend_from_slice(&self.max_bits.to_le_bytes());
+        for (p, j) in &self.entries {
+            bytes.extend_from_slice(&p.to_le_bytes());
+            bytes.extend_from_slice(&j.to_le_bytes());
+        }
+        return bytes;
+    }
+
+    #[allow(non_snake_case)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<DiscreteLogTable, DecodeError> {
+        if bytes.len() < 40 {
+            return Err(DecodeError::WrongLength);
+        }
+        let G = read_point(bytes)?;
+        let mut word = [0u8; 4];
+        word.copy_from_slice(&bytes[32..36]);
+        let table_bits = u32::from_le_bytes(word);
+        word.copy_from_slice(&bytes[36..40]);
+        let max_bits = u32::from_le_bytes(word);
+        if table_bits > 32
+            || max_bits > 64
+            || table_bits > max_bits
+            || (bytes.len() - 40) as u64 != 12u64 << table_bits
+        {
+            return Err(DecodeError::WrongLength);
+        }
+
+        let mut entries = Vec::with_capacity(1usize << table_bits);
+        for entry in bytes[40..].chunks(12) {
+            let mut p = [0u8; 8];
+            p.copy_from_slice(&entry[0..8]);
+            word.copy_from_slice(&entry[8..12]);
+            entries.push((u64::from_le_bytes(p), u32::from_le_bytes(word)));
+        }
+        //get relies on the order
+        if entries.windows(2).any(|w| w[0] > w[1]) {
+            return Err(DecodeError::WrongLength);
+        }
+        return Ok(DiscreteLogTable {
+            G,
+            table_bits,
+            max_bits,
+            entries,
+        });
+    }
+}
+
+#[cfg(feature = "std")]
+impl DiscreteLogTable {
+    pub fn save(&self, path: &std::path::Path) -> std::io::Result<()> {
+        return std::fs::write(path, self.to_bytes());
+    }
+
+    pub fn load(path: &std::path::Path) -> std::io::Result<DiscreteLogTable> {
+        let bytes = std::fs::read(path)?;
+        return DiscreteLogTable::from_bytes(&bytes).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid discrete log table",
+            )
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::OsRng;
+    use rand_core::RngCore;
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_discrete_log_table() {
+        let G = get_G();
+        let table = DiscreteLogTable::new(G, 8, 16);
+        let mut csprng: OsRng = OsRng::default();
+        for _ in 0..10 {
+            let v = csprng.next_u64() & 0xffff;
+            assert_eq!(table.get(&(Scalar::from(v) * G)), Some(v));
+        }
+        assert_eq!(table.get(&EdwardsPoint::default()), Some(0));
+        assert_eq!(table.get(&(Scalar::from(1u64 << 16) * G)), None);
+
+        let bytes = table.to_bytes();
+        assert_eq!(DiscreteLogTable::from_bytes(&bytes), Ok(table.clone()));
+        assert_eq!(
+            DiscreteLogTable::from_bytes(&bytes[0..bytes.len() - 12]),
+            Err(DecodeError::WrongLength)
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_discrete_log_table_file() {
+        let G = get_G();
+        let table = DiscreteLogTable::new(G, 6, 12);
+        let path = std::env::temp_dir().join("zkplmt_test_discrete_log_table");
+        table.save(&path).unwrap();
+        let loaded = DiscreteLogTable::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, table);
+        assert_eq!(loaded.get(&(Scalar::from(3000u64) * G)), Some(3000));
+    }
+}
@@ -1,16 +1,12 @@
 use super::core::*;
-use rand_core::RngCore;
 use rand_core::OsRng;
-//use crypto::digest::Digest as Dgst;
+use alloc::collections::BTreeMap;
 use alloc::vec::*;
-use sha2::Sha256;
-use sha2::Digest;
-use curve25519_dalek::edwards::CompressedEdwardsY;
 use curve25519_dalek::edwards::EdwardsPoint;
 use curve25519_dalek::scalar::Scalar;
-use curve25519_dalek::traits::IsIdentity;
-use sha2::Sha512;
+use serde::*;
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct KYCProof {
     new_public_key: CurveVector,
     ap_recoverable_keys: CurveVector,
@@ -18,103 +14,375 @@ pub struct KYCProof {
     r: Vec<Scalar>,
 }
 
-// pub fn create_kyc_shuffle(
-//         tuples: &mut [VectorTuple],
-//         secret: Scalar,
-//     ) -> KYCProof {
-//         let hidden_index = shuffle(tuples);
-//         return create_kyc(tuples, hidden_index, secret);
-//     }
+impl KYCProof {
+    //(H, secret*H) for H = hash_to_edwards(T)
+    pub fn new_public_key(&self) -> &CurveVector {
+        return &self.new_public_key;
+    }
+
+    //(Aa, secret*Aa) for Aa = sum of r_i*ap_bases[i]
+    pub fn ap_recoverable_keys(&self) -> &CurveVector {
+        return &self.ap_recoverable_keys;
+    }
+
+    pub fn proof(&self) -> &Proof {
+        return &self.proof;
+    }
+
+    pub fn r(&self) -> &[Scalar] {
+        return &self.r;
+    }
+}
+
+//The AP bases and H = hash_to_edwards(T) shared by KycProver and KycVerifier
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq)]
+struct KycContext {
+    ap_bases: Vec<EdwardsPoint>,
+    H: EdwardsPoint,
+}
+
+//Proves many KYCs against the same AP bases and T, hashing T once
+#[derive(Clone, Debug, PartialEq)]
+pub struct KycProver {
+    context: KycContext,
+}
+
+//Verifies many KYCs against the same AP bases and T, hashing T once
+#[derive(Clone, Debug, PartialEq)]
+pub struct KycVerifier {
+    context: KycContext,
+}
+
+impl KycProver {
+    #[allow(non_snake_case)]
+    pub fn new(ap_bases: &[EdwardsPoint], T: &EdwardsPoint) -> KycProver {
+        return KycProver {
+            context: KycContext::new(ap_bases, T),
+        };
+    }
+
+    pub fn prove(
+        &self,
+        sources: &[CurveVector],
+        hidden_index: usize,
+        secret: Scalar,
+    ) -> (Scalar, KYCProof) {
+        return self.context.prove(sources, hidden_index, secret);
+    }
+}
+
+impl KycVerifier {
+    #[allow(non_snake_case)]
+    pub fn new(ap_bases: &[EdwardsPoint], T: &EdwardsPoint) -> KycVerifier {
+        return KycVerifier {
+            context: KycContext::new(ap_bases, T),
+        };
+    }
+
+    #[allow(non_snake_case)]
+    pub fn verify(
+        &self,
+        sources: &[CurveVector],
+        sources_S: &[EdwardsPoint],
+        kyc_proof: &KYCProof,
+        U: &EdwardsPoint,
+    ) -> bool {
+        return self.context.verify(sources, sources_S, kyc_proof, U);
+    }
+}
+
+impl KycContext {
+    #[allow(non_snake_case)]
+    fn new(ap_bases: &[EdwardsPoint], T: &EdwardsPoint) -> KycContext {
+        return KycContext {
+            ap_bases: ap_bases.to_vec(),
+            H: hash_to_edwards(&T.compress().to_bytes()),
+        };
+    }
+
+    #[allow(non_snake_case)]
+    fn prove(
+        &self,
+        sources: &[CurveVector],
+        hidden_index: usize,
+        secret: Scalar,
+    ) -> (Scalar, KYCProof) {
+        let mut csprng: OsRng = OsRng::default();
+        let mut Aa = EdwardsPoint::default();
+        let mut rs = Vec::new();
+        for i in 0..self.ap_bases.len() {
+            let r = Scalar::random(&mut csprng);
+            Aa += r * self.ap_bases[i];
+            rs.push(r);
+        }
+        let Ba = secret * Aa;
+        let H = self.H;
+        let pH = secret * H;
+        let s = Scalar::random(&mut csprng);
+        let Sa = s * Aa;
+        let sBa = s * Ba;
+        let mut input_tuples = Vec::new();
+        for source in sources {
+            let mut vector_tuple = Vec::new();
+            vector_tuple.push(*source);
+            vector_tuple.push(CurveVector { x: H, y: pH });
+            vector_tuple.push(CurveVector { x: Aa, y: Ba });
+            vector_tuple.push(CurveVector { x: Sa, y: sBa });
+
+            input_tuples.push(VectorTuple {
+                values: vector_tuple,
+            });
+        }
+        let proof = create_zkplmt(&input_tuples, hidden_index, secret);
+        return (
+            s,
+            KYCProof {
+                new_public_key: CurveVector { x: H, y: pH },
+                ap_recoverable_keys: CurveVector { x: Aa, y: Ba },
+                proof: proof,
+                r: rs,
+            },
+        );
+    }
+
+    #[allow(non_snake_case)]
+    fn verify(
+        &self,
+        sources: &[CurveVector],
+        sources_S: &[EdwardsPoint],
+        kyc_proof: &KYCProof,
+        U: &EdwardsPoint,
+    ) -> bool {
+        if self.H != kyc_proof.new_public_key.x {
+            return false;
+        }
+        if kyc_proof.r.len() != self.ap_bases.len() || sources_S.len() != sources.len() {
+            return false;
+        }
+
+        let mut Aa = EdwardsPoint::default();
+        for i in 0..self.ap_bases.len() {
+            let r = kyc_proof.r[i];
+            Aa += r * self.ap_bases[i];
+        }
+        if Aa != kyc_proof.ap_recoverable_keys.x {
+            return false;
+        }
+        let Ba = kyc_proof.ap_recoverable_keys.y;
+
+        let mut input_tuples = Vec::new();
+
+        for i in 0..sources.len() {
+            let mut vector_tuple = Vec::new();
+            let source = sources[i];
+            vector_tuple.push(source);
+            vector_tuple.push(kyc_proof.new_public_key);
+            vector_tuple.push(CurveVector { x: Aa, y: Ba });
+            vector_tuple.push(CurveVector {
+                x: sources_S[i],
+                y: *U,
+            });
+
+            input_tuples.push(VectorTuple {
+                values: vector_tuple,
+            });
+        }
+        return verify_zkplmt(&input_tuples, &kyc_proof.proof);
+    }
+}
+
 //Sa and sBa are required only when the KYC proof is provided at spend.
 //But anyway the AP traceability cannot be done from the sending side, so in case
 // of KYC proof at send, we do not need
 #[allow(non_snake_case)]
 pub fn create_kyc(
-    sources: Vec<CurveVector>,
+    sources: &[CurveVector],
     hidden_index: usize,
-    ap_bases: &mut [EdwardsPoint],
-    T: EdwardsPoint,
+    ap_bases: &[EdwardsPoint],
+    T: &EdwardsPoint,
     secret: Scalar,
 ) -> (Scalar, KYCProof) {
-    let mut csprng: OsRng = OsRng::default();
-    let mut Aa = EdwardsPoint::default();
-    let mut rs = Vec::new();
-    for i in 0..ap_bases.len() {
-        let r = Scalar::random(&mut csprng);
-        Aa += r * ap_bases[i];
-        rs.push(r);
-    }
-    let Ba = secret * Aa;
-    let H = hash_to_edwards(&T.compress().to_bytes());
-    let pH = secret * H;
-    let s = Scalar::random(&mut csprng);
-    let Sa = s * Aa;
-    let sBa = s * Ba;
-    let mut input_tuples = Vec::new();
-    for source in sources {
-        let mut vector_tuple = Vec::new();
-        vector_tuple.push(source);
-        vector_tuple.push(CurveVector { x: H, y: pH });
-        vector_tuple.push(CurveVector { x: Aa, y: Ba });
-        vector_tuple.push(CurveVector { x: Sa, y: sBa });
-
-        input_tuples.push(VectorTuple {
-            values: vector_tuple,
-        });
-    }
-    let proof = create_zkplmt(&input_tuples, hidden_index, secret);
-    return (
-        s,
-        KYCProof {
-            new_public_key: CurveVector { x: H, y: pH },
-            ap_recoverable_keys: CurveVector { x: Aa, y: Ba },
-            proof: proof,
-            r: rs,
-        },
-    );
+    return KycProver::new(ap_bases, T).prove(sources, hidden_index, secret);
 }
 
 #[allow(non_snake_case)]
 pub fn verify_kyc(
-    sources: Vec<CurveVector>,
-    sources_S: Vec<EdwardsPoint>,
-    ap_bases: &mut [EdwardsPoint],
-    T: EdwardsPoint,
-    kyc_proof: KYCProof,
-    U: EdwardsPoint,
+    sources: &[CurveVector],
+    sources_S: &[EdwardsPoint],
+    ap_bases: &[EdwardsPoint],
+    T: &EdwardsPoint,
+    kyc_proof: &KYCProof,
+    U: &EdwardsPoint,
 ) -> bool {
-    let H = hash_to_edwards(&T.compress().to_bytes());
+    return KycVerifier::new(ap_bases, T).verify(sources, sources_S, kyc_proof, U);
+}
+
+pub type IdentityIndex = usize;
+
+//The public keys secret*G of the registered users, indexed by their compressed bytes
+#[derive(Clone, Debug, Default)]
+pub struct IdentityRegistry {
+    keys: BTreeMap<[u8; 32], IdentityIndex>,
+    len: usize,
+}
+
+impl IdentityRegistry {
+    pub fn new(public_keys: &[EdwardsPoint]) -> IdentityRegistry {
+        let mut registry = IdentityRegistry::default();
+        for key in public_keys {
+            registry.register(key);
+        }
+        return registry;
+    }
 
-    if H != kyc_proof.new_public_key.x {
-        return false;
+    //Returns the index of the key, the one it was given first if it was registered before
+    pub fn register(&mut self, public_key: &EdwardsPoint) -> IdentityIndex {
+        let index = self.len;
+        let index = *self
+            .keys
+            .entry(public_key.compress().to_bytes())
+            .or_insert(index);
+        if index == self.len {
+            self.len += 1;
+        }
+        return index;
     }
 
-    let mut Aa = EdwardsPoint::default();
-    for i in 0..ap_bases.len() {
-        let r = kyc_proof.r[i];
-        Aa += r * ap_bases[i];
+    pub fn get(&self, public_key: &EdwardsPoint) -> Option<IdentityIndex> {
+        return self.keys.get(&public_key.compress().to_bytes()).cloned();
     }
-    if Aa != kyc_proof.ap_recoverable_keys.x {
-        return false;
+
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len == 0;
+    }
+}
+
+//The AP knows b_i with ap_bases[i] = b_i*G, so Aa = w*G for w = sum of r_i*b_i and
+//Ba = secret*Aa gives the public key secret*G = w^-1*Ba of the user. None if the proof was
+//not made for the bases of these secrets or the user is not registered.
+pub fn trace_kyc(
+    kyc_proof: &KYCProof,
+    ap_secrets: &[Scalar],
+    registered_identities: &IdentityRegistry,
+) -> Option<IdentityIndex> {
+    if ap_secrets.len() != kyc_proof.r.len() {
+        return None;
+    }
+    let w = kyc_proof
+        .r
+        .iter()
+        .zip(ap_secrets.iter())
+        .fold(Scalar::zero(), |sum, (r, b)| sum + r * b);
+    if w == Scalar::zero() || w * get_G() != kyc_proof.ap_recoverable_keys.x {
+        return None;
+    }
+    let public_key = w.invert() * kyc_proof.ap_recoverable_keys.y;
+    return registered_identities.get(&public_key);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_trace_kyc() {
+        let mut csprng: OsRng = OsRng::default();
+        let G = get_G();
+        let secrets: Vec<Scalar> = (0..5).map(|_| Scalar::random(&mut csprng)).collect();
+        let registry =
+            IdentityRegistry::new(&secrets.iter().map(|p| p * G).collect::<Vec<EdwardsPoint>>());
+        assert_eq!(registry.len(), 5);
+
+        //the sources of the proof, the user behind source 3 has identity 2
+        let mut sources: Vec<CurveVector> = (0..5)
+            .map(|_| {
+                let x = get_random_curve_point();
+                CurveVector {
+                    x: x,
+                    y: Scalar::random(&mut csprng) * x,
+                }
+            })
+            .collect();
+        sources[3].y = secrets[2] * sources[3].x;
+
+        let ap_secrets: Vec<Scalar> = (0..3).map(|_| Scalar::random(&mut csprng)).collect();
+        let ap_bases: Vec<EdwardsPoint> = ap_secrets.iter().map(|b| b * G).collect();
+        let T = get_random_curve_point();
+        let (_, kyc_proof) = create_kyc(&sources, 3, &ap_bases, &T, secrets[2]);
+
+        assert_eq!(trace_kyc(&kyc_proof, &ap_secrets, &registry), Some(2));
+        let other_secrets: Vec<Scalar> = (0..3).map(|_| Scalar::random(&mut csprng)).collect();
+        assert_eq!(trace_kyc(&kyc_proof, &other_secrets, &registry), None);
+        assert_eq!(
+            trace_kyc(
+                &kyc_proof,
+                &ap_secrets,
+                &IdentityRegistry::new(
+                    &secrets[3..]
+                        .iter()
+                        .map(|p| p * G)
+                        .collect::<Vec<EdwardsPoint>>()
+                )
+            ),
+            None
+        );
     }
-    let Ba = kyc_proof.ap_recoverable_keys.y;
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_kyc_prover_verifier() {
+        let mut csprng: OsRng = OsRng::default();
+        let secret = Scalar::random(&mut csprng);
+        let mut sources: Vec<CurveVector> = (0..4)
+            .map(|_| {
+                let x = get_random_curve_point();
+                CurveVector {
+                    x: x,
+                    y: Scalar::random(&mut csprng) * x,
+                }
+            })
+            .collect();
+        sources[1].y = secret * sources[1].x;
+        let ap_bases: Vec<EdwardsPoint> = (0..3).map(|_| get_random_curve_point()).collect();
+        let T = get_random_curve_point();
 
-    let mut input_tuples = Vec::new();
+        let prover = KycProver::new(&ap_bases, &T);
+        let verifier = KycVerifier::new(&ap_bases, &T);
+        for _ in 0..2 {
+            let (s, kyc_proof) = prover.prove(&sources, 1, secret);
+            assert_eq!(kyc_proof.r().len(), 3);
+            assert_eq!(
+                kyc_proof.new_public_key().y,
+                secret * kyc_proof.new_public_key().x
+            );
+            let sources_S = vec![s * kyc_proof.ap_recoverable_keys().x; sources.len()];
+            let U = s * kyc_proof.ap_recoverable_keys().y;
+            assert!(verifier.verify(&sources, &sources_S, &kyc_proof, &U));
 
-    for i in 0..sources.len() {
-        let mut vector_tuple = Vec::new();
-        let source = sources[i];
-        vector_tuple.push(source);
-        vector_tuple.push(kyc_proof.new_public_key);
-        vector_tuple.push(CurveVector { x: Aa, y: Ba });
-        vector_tuple.push(CurveVector {
-            x: sources_S[i],
-            y: U,
-        });
+            let encoded = bincode::serialize(&kyc_proof).unwrap();
+            let decoded: KYCProof = bincode::deserialize(&encoded).unwrap();
+            assert_eq!(decoded, kyc_proof);
+            assert!(verify_kyc(
+                &sources, &sources_S, &ap_bases, &T, &decoded, &U
+            ));
 
-        input_tuples.push(VectorTuple {
-            values: vector_tuple,
-        });
+            assert!(!verify_kyc(
+                &sources,
+                &sources_S,
+                &ap_bases,
+                &get_random_curve_point(),
+                &decoded,
+                &U
+            ));
+            assert!(!verifier.verify(&sources, &sources_S[1..], &kyc_proof, &U));
+            assert!(
+                !KycVerifier::new(&ap_bases[1..], &T).verify(&sources, &sources_S, &kyc_proof, &U)
+            );
+        }
     }
-    return verify_zkplmt(&input_tuples, &kyc_proof.proof);
 }
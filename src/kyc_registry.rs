@@ -0,0 +1,544 @@
+//The registry of the KYC'd sources. The issuer appends the identity keys of the users it has
+//checked and signs epochs (epoch number, size, Merkle root) of the registry. A prover takes the
+//ring of create_kyc from the registry together with the inclusion paths of its members, and a
+//verifier checks the ring against the root of a signed epoch before verify_kyc.
+//
+//The tree is the one of RFC 6962, leaves are hashed with a 0 byte in front and nodes with a 1.
+//Every epoch carries the consistency proof of RFC 6962 from the previous epoch, which shows
+//the older tree is a prefix of the newer one, so a verifier following the epochs can tell an
+//extended registry from a rewritten one and a source keeps its index in every later epoch.
+//The epoch signature is a zkplmt over [(G, issuer public key)] with the epoch as message.
+
+use super::core::*;
+use alloc::vec::*;
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::scalar::Scalar;
+use serde::*;
+use sha2::Digest;
+use sha2::Sha256;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct KycRegistry {
+    sources: Vec<CurveVector>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignedEpoch {
+    epoch: u64,
+    size: u64,
+    root: [u8; 32],
+    signature: Proof,
+    //from the registry of the previous epoch
+    consistency: Vec<[u8; 32]>,
+}
+
+//Where the members of a ring are in the registry of an epoch
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RingMembership {
+    size: u64,
+    indices: Vec<u64>,
+    paths: Vec<Vec<[u8; 32]>>,
+}
+
+pub struct KycIssuer {
+    secret: Scalar,
+    public_key: EdwardsPoint,
+    registry: KycRegistry,
+    epoch: u64,
+    //the size of the registry at the last signed epoch
+    signed_size: usize,
+}
+
+fn leaf_hash(source: &CurveVector) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.input(&[0u8]);
+    hasher.input(&source.x.compress().to_bytes());
+    hasher.input(&source.y.compress().to_bytes());
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(hasher.result().as_slice());
+    return hash;
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.input(&[1u8]);
+    hasher.input(left);
+    hasher.input(right);
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(hasher.result().as_slice());
+    return hash;
+}
+
+//The largest power of 2 less than size, for size > 1. The sizes of a SignedEpoch or a
+//RingMembership come from untrusted data, so this is computed in u64 for any of them.
+fn split(size: u64) -> u64 {
+    return match size.checked_next_power_of_two() {
+        Some(power) => power / 2,
+        None => 1 << 63,
+    };
+}
+
+fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    if leaves.is_empty() {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(Sha256::new().result().as_slice());
+        return hash;
+    }
+    if leaves.len() == 1 {
+        return leaves[0];
+    }
+    let k = split(leaves.len() as u64) as usize;
+    return node_hash(&merkle_root(&leaves[..k]), &merkle_root(&leaves[k..]));
+}
+
+fn inclusion_path(index: usize, leaves: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    if leaves.len() <= 1 {
+        return Vec::new();
+    }
+    let k = split(leaves.len() as u64) as usize;
+    let mut path;
+    if index < k {
+        path = inclusion_path(index, &leaves[..k]);
+        path.push(merkle_root(&leaves[k..]));
+    } else {
+        path = inclusion_path(index - k, &leaves[k..]);
+        path.push(merkle_root(&leaves[..k]));
+    }
+    return path;
+}
+
+//The root of a tree of size leaves with leaf at index and the given path, None if the path
+//does not have the length for index and size
+fn root_from_path(index: u64, size: u64, leaf: [u8; 32], path: &[[u8; 32]]) -> Option<[u8; 32]> {
+    if size == 1 {
+        return if path.is_empty() { Some(leaf) } else { None };
+    }
+    let (last, rest) = path.split_last()?;
+    let k = split(size);
+    if index < k {
+        return Some(node_hash(&root_from_path(index, k, leaf, rest)?, last));
+    }
+    return Some(node_hash(
+        last,
+        &root_from_path(index - k, size - k, leaf, rest)?,
+    ));
+}
+
+//SUBPROOF of RFC 6962, complete is true while the old tree is the whole of leaves[..old_size]
+fn consistency_subpath(old_size: usize, leaves: &[[u8; 32]], complete: bool) -> Vec<[u8; 32]> {
+    if old_size == leaves.len() {
+        return if complete {
+            Vec::new()
+        } else {
+            vec![merkle_root(leaves)]
+        };
+    }
+    let k = split(leaves.len() as u64) as usize;
+    let mut path;
+    if old_size <= k {
+        path = consistency_subpath(old_size, &leaves[..k], complete);
+        path.push(merkle_root(&leaves[k..]));
+    } else {
+        path = consistency_subpath(old_size - k, &leaves[k..], false);
+        path.push(merkle_root(&leaves[..k]));
+    }
+    return path;
+}
+
+fn consistency_path(old_size: usize, leaves: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    if old_size == 0 {
+        return Vec::new();
+    }
+    return consistency_subpath(old_size, leaves, true);
+}
+
+//The old and the new root of the subtree of size new_size a consistency path gives, None if the
+//path does not have the length for the sizes
+fn roots_from_consistency(
+    old_size: u64,
+    new_size: u64,
+    complete: bool,
+    old_root: &[u8; 32],
+    path: &[[u8; 32]],
+) -> Option<([u8; 32], [u8; 32])> {
+    if old_size == new_size {
+        if complete {
+            return if path.is_empty() {
+                Some((*old_root, *old_root))
+            } else {
+                None
+            };
+        }
+        return if path.len() == 1 {
+            Some((path[0], path[0]))
+        } else {
+            None
+        };
+    }
+    let (last, rest) = path.split_last()?;
+    let k = split(new_size);
+    if old_size <= k {
+        let (old, new) = roots_from_consistency(old_size, k, complete, old_root, rest)?;
+        return Some((old, node_hash(&new, last)));
+    }
+    let (old, new) = roots_from_consistency(old_size - k, new_size - k, false, old_root, rest)?;
+    return Some((node_hash(last, &old), node_hash(last, &new)));
+}
+
+fn epoch_message(epoch: u64, size: u64, root: &[u8; 32]) -> Vec<u8> {
+    let mut message = b"KYC registry epoch".to_vec();
+    message.extend_from_slice(&epoch.to_le_bytes());
+    message.extend_from_slice(&size.to_le_bytes());
+    message.extend_from_slice(root);
+    return message;
+}
+
+fn issuer_tuples(public_key: EdwardsPoint) -> Vec<VectorTuple> {
+    return vec![VectorTuple {
+        values: vec![CurveVector {
+            x: get_G(),
+            y: public_key,
+        }],
+    }];
+}
+
+impl KycRegistry {
+    pub fn new() -> KycRegistry {
+        return KycRegistry::default();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.sources.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.sources.is_empty();
+    }
+
+    pub fn sources(&self) -> &[CurveVector] {
+        return &self.sources;
+    }
+
+    fn leaves(&self) -> Vec<[u8; 32]> {
+        return self.sources.iter().map(leaf_hash).collect();
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        return merkle_root(&self.leaves());
+    }
+
+    //The proof that the registry when it had old_size sources is a prefix of this one, None if
+    //old_size is larger than the registry
+    pub fn consistency_proof(&self, old_size: usize) -> Option<Vec<[u8; 32]>> {
+        if old_size > self.sources.len() {
+            return None;
+        }
+        return Some(consistency_path(old_size, &self.leaves()));
+    }
+
+    //The sources at indices to give create_kyc and their membership, None if an index is out
+    //of the registry or repeated
+    pub fn ring(&self, indices: &[usize]) -> Option<(Vec<CurveVector>, RingMembership)> {
+        for i in 0..indices.len() {
+            if indices[i] >= self.sources.len() || indices[..i].contains(&indices[i]) {
+                return None;
+            }
+        }
+        let leaves = self.leaves();
+        return Some((
+            indices.iter().map(|i| self.sources[*i]).collect(),
+            RingMembership {
+                size: self.sources.len() as u64,
+                indices: indices.iter().map(|i| *i as u64).collect(),
+                paths: indices
+                    .iter()
+                    .map(|i| inclusion_path(*i, &leaves))
+                    .collect(),
+            },
+        ));
+    }
+}
+
+impl SignedEpoch {
+    pub fn epoch(&self) -> u64 {
+        return self.epoch;
+    }
+
+    pub fn size(&self) -> u64 {
+        return self.size;
+    }
+
+    pub fn root(&self) -> &[u8; 32] {
+        return &self.root;
+    }
+
+    pub fn consistency(&self) -> &[[u8; 32]] {
+        return &self.consistency;
+    }
+}
+
+impl RingMembership {
+    pub fn indices(&self) -> &[u64] {
+        return &self.indices;
+    }
+}
+
+impl KycIssuer {
+    pub fn new(secret: Scalar) -> KycIssuer {
+        return KycIssuer {
+            secret,
+            public_key: secret * get_G(),
+            registry: KycRegistry::new(),
+            epoch: 0,
+            signed_size: 0,
+        };
+    }
+
+    pub fn public_key(&self) -> EdwardsPoint {
+        return self.public_key;
+    }
+
+    pub fn registry(&self) -> &KycRegistry {
+        return &self.registry;
+    }
+
+    //Appends the identity key of a checked user, returns its index in the registry
+    pub fn register(&mut self, source: CurveVector) -> usize {
+        self.registry.sources.push(source);
+        return self.registry.sources.len() - 1;
+    }
+
+    //Signs the registry as it is now as the next epoch
+    pub fn sign_epoch(&mut self) -> SignedEpoch {
+        self.epoch += 1;
+        let size = self.registry.len() as u64;
+        let root = self.registry.root();
+        let signature = create_zkplmt_with_message(
+            &issuer_tuples(self.public_key),
+            0,
+            self.secret,
+            &epoch_message(self.epoch, size, &root),
+        );
+        let consistency = consistency_path(self.signed_size, &self.registry.leaves());
+        self.signed_size = self.registry.len();
+        return SignedEpoch {
+            epoch: self.epoch,
+            size,
+            root,
+            signature,
+            consistency,
+        };
+    }
+}
+
+pub fn verify_epoch(issuer_public_key: &EdwardsPoint, epoch: &SignedEpoch) -> bool {
+    return verify_zkplmt_with_message(
+        &issuer_tuples(*issuer_public_key),
+        &epoch.signature,
+        &epoch_message(epoch.epoch, epoch.size, &epoch.root),
+    );
+}
+
+//Checks the registry of the new epoch extends the one of the old epoch with the path of
+//KycRegistry::consistency_proof. The signatures are checked by verify_epoch.
+pub fn verify_consistency(old: &SignedEpoch, new: &SignedEpoch, path: &[[u8; 32]]) -> bool {
+    if old.size > new.size {
+        return false;
+    }
+    if old.size == 0 {
+        return path.is_empty();
+    }
+    return roots_from_consistency(old.size, new.size, true, &old.root, path)
+        == Some((old.root, new.root));
+}
+
+//Checks epoch is the one after previous and its registry extends the previous one
+pub fn verify_epoch_extends(previous: &SignedEpoch, epoch: &SignedEpoch) -> bool {
+    return epoch.epoch == previous.epoch + 1
+        && verify_consistency(previous, epoch, &epoch.consistency);
+}
+
+//Checks the ring is in the registry of the epoch, the epoch itself is checked by verify_epoch
+pub fn verify_ring(epoch: &SignedEpoch, ring: &[CurveVector], membership: &RingMembership) -> bool {
+    if membership.size != epoch.size
+        || membership.indices.len() != ring.len()
+        || membership.paths.len() != ring.len()
+    {
+        return false;
+    }
+    for i in 0..ring.len() {
+        let index = membership.indices[i];
+        if index >= epoch.size || membership.indices[..i].contains(&index) {
+            return false;
+        }
+        let root = root_from_path(index, epoch.size, leaf_hash(&ring[i]), &membership.paths[i]);
+        if root != Some(epoch.root) {
+            return false;
+        }
+    }
+    return true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::kyc_proof::*;
+    use super::*;
+    use rand_core::OsRng;
+
+    fn random_source() -> CurveVector {
+        let mut csprng: OsRng = OsRng::default();
+        let x = get_random_curve_point();
+        return CurveVector {
+            x: x,
+            y: Scalar::random(&mut csprng) * x,
+        };
+    }
+
+    #[test]
+    fn test_inclusion_paths() {
+        let mut registry = KycRegistry::new();
+        let mut roots = vec![registry.root()];
+        for size in 1..=9 {
+            registry.sources.push(random_source());
+            let leaves = registry.leaves();
+            for index in 0..size {
+                let path = inclusion_path(index, &leaves);
+                assert_eq!(
+                    root_from_path(index as u64, size as u64, leaves[index], &path),
+                    Some(registry.root())
+                );
+                if index ^ 1 < size {
+                    assert_ne!(
+                        root_from_path((index ^ 1) as u64, size as u64, leaves[index], &path),
+                        Some(registry.root())
+                    );
+                }
+            }
+            assert!(!roots.contains(&registry.root()));
+            roots.push(registry.root());
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_registry_kyc() {
+        let mut csprng: OsRng = OsRng::default();
+        let mut issuer = KycIssuer::new(Scalar::random(&mut csprng));
+        let secret = Scalar::random(&mut csprng);
+        let x = get_random_curve_point();
+        for i in 0..7 {
+            if i == 4 {
+                issuer.register(CurveVector { x, y: secret * x });
+            } else {
+                issuer.register(random_source());
+            }
+        }
+        let epoch = issuer.sign_epoch();
+        assert_eq!(epoch.epoch(), 1);
+        assert_eq!(epoch.size(), 7);
+        assert!(verify_epoch(&issuer.public_key(), &epoch));
+
+        let (ring, membership) = issuer.registry().ring(&[6, 4, 0, 2]).unwrap();
+        assert!(issuer.registry().ring(&[6, 4, 6]).is_none());
+        assert!(issuer.registry().ring(&[7]).is_none());
+        let ap_bases: Vec<EdwardsPoint> = (0..2).map(|_| get_random_curve_point()).collect();
+        let T = get_random_curve_point();
+        let (s, kyc_proof) = create_kyc(&ring, 1, &ap_bases, &T, secret);
+
+        assert!(verify_ring(&epoch, &ring, &membership));
+        let sources_S = vec![s * kyc_proof.ap_recoverable_keys().x; ring.len()];
+        let U = s * kyc_proof.ap_recoverable_keys().y;
+        assert!(verify_kyc(&ring, &sources_S, &ap_bases, &T, &kyc_proof, &U));
+
+        //a source that was not registered
+        let mut other_ring = ring.clone();
+        other_ring[2] = random_source();
+        assert!(!verify_ring(&epoch, &other_ring, &membership));
+
+        //the registry grew, the old membership is not for the new epoch
+        issuer.register(random_source());
+        let next_epoch = issuer.sign_epoch();
+        assert_eq!(next_epoch.epoch(), 2);
+        assert!(!verify_ring(&next_epoch, &ring, &membership));
+        let (_, new_membership) = issuer.registry().ring(&[6, 4, 0, 2]).unwrap();
+        assert!(verify_ring(&next_epoch, &ring, &new_membership));
+
+        let mut forged = next_epoch.clone();
+        forged.root = epoch.root;
+        assert!(!verify_epoch(&issuer.public_key(), &forged));
+        assert!(!verify_epoch(&get_random_curve_point(), &next_epoch));
+    }
+    #[test]
+    fn test_consistency() {
+        let mut csprng: OsRng = OsRng::default();
+        let mut issuer = KycIssuer::new(Scalar::random(&mut csprng));
+        let mut epochs = vec![issuer.sign_epoch()];
+        for size in 1..=9 {
+            issuer.register(random_source());
+            epochs.push(issuer.sign_epoch());
+            assert!(verify_epoch_extends(&epochs[size - 1], &epochs[size]));
+            for old_size in 0..=size {
+                let path = issuer.registry().consistency_proof(old_size).unwrap();
+                assert!(verify_consistency(&epochs[old_size], &epochs[size], &path));
+                if old_size > 0 && old_size < size {
+                    let mut bad_path = path.clone();
+                    bad_path[0][0] ^= 1;
+                    assert!(!verify_consistency(
+                        &epochs[old_size],
+                        &epochs[size],
+                        &bad_path
+                    ));
+                }
+            }
+        }
+        assert!(issuer.registry().consistency_proof(10).is_none());
+        assert!(!verify_epoch_extends(&epochs[3], &epochs[5]));
+
+        //the issuer rewrites source 2 and appends one
+        issuer.registry.sources[2] = random_source();
+        issuer.register(random_source());
+        let rewritten = issuer.sign_epoch();
+        assert!(verify_epoch(&issuer.public_key(), &rewritten));
+        assert!(!verify_epoch_extends(&epochs[9], &rewritten));
+        for old_size in 3..=9 {
+            let path = issuer.registry().consistency_proof(old_size).unwrap();
+            assert!(!verify_consistency(&epochs[old_size], &rewritten, &path));
+        }
+        //the sources before the rewritten one are untouched
+        let path = issuer.registry().consistency_proof(2).unwrap();
+        assert!(verify_consistency(&epochs[2], &rewritten, &path));
+    }
+    #[test]
+    fn test_untrusted_sizes() {
+        assert_eq!(split(2), 1);
+        assert_eq!(split(5), 4);
+        assert_eq!(split(8), 4);
+        assert_eq!(split((1 << 63) + 1), 1 << 63);
+        assert_eq!(split(u64::MAX), 1 << 63);
+
+        let mut csprng: OsRng = OsRng::default();
+        let mut issuer = KycIssuer::new(Scalar::random(&mut csprng));
+        for _ in 0..5 {
+            issuer.register(random_source());
+        }
+        let old = issuer.sign_epoch();
+        issuer.register(random_source());
+        let new = issuer.sign_epoch();
+        let (ring, membership) = issuer.registry().ring(&[1, 4]).unwrap();
+        let path = issuer.registry().consistency_proof(5).unwrap();
+        assert!(verify_ring(&new, &ring, &membership));
+        assert!(verify_consistency(&old, &new, &path));
+
+        for size in [1u64 << 32, (1 << 63) + 1, u64::MAX].iter() {
+            let mut forged = new.clone();
+            forged.size = *size;
+            let mut forged_membership = membership.clone();
+            forged_membership.size = *size;
+            assert!(!verify_ring(&forged, &ring, &forged_membership));
+            assert!(!verify_consistency(&old, &forged, &path));
+            let mut forged_old = old.clone();
+            forged_old.size = *size - 1;
+            assert!(!verify_consistency(&forged_old, &forged, &path));
+        }
+    }
+}
@@ -0,0 +1,326 @@
+//Threshold tracing of KYC proofs, any k of the n auditors together can do what a single AP
+//does in trace_kyc, fewer cannot.
+//
+//The AP secret is b and ap_bases[i] = (b*x_i)*G for public x_i, so a KYC proof has
+//Ba = secret*b*a*G for a = sum of r_i*x_i. The auditors hold Shamir shares d_j of d = 1/b.
+//Auditor j publishes D_j = d_j*Ba with a zkplmt over [(G, d_j*G), (Ba, D_j)] showing it used its
+//share, and the Lagrange combination of k of them is d*Ba = secret*a*G, which a^-1 takes to the
+//public key secret*G of the user.
+
+use super::core::*;
+use super::kyc_proof::*;
+use alloc::vec::*;
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::scalar::Scalar;
+use rand_core::OsRng;
+use serde::*;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ThresholdApKey {
+    ap_bases: Vec<EdwardsPoint>,
+    x: Vec<Scalar>,
+    //d_j*G of auditor j at index j - 1
+    verification_keys: Vec<EdwardsPoint>,
+    threshold: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditorShare {
+    //x coordinate of the share, 1..n
+    index: u64,
+    d: Scalar,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PartialDecryption {
+    index: u64,
+    D: EdwardsPoint,
+    proof: Proof,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThresholdTraceError {
+    //the KYC proof was not made for the ap_bases of the key
+    ProofNotForKey,
+    NotEnoughPartials { required: usize, available: usize },
+    InvalidPartial { index: u64 },
+    DuplicatePartial { index: u64 },
+    NotRegistered,
+}
+
+impl ThresholdApKey {
+    //The bases to give create_kyc
+    pub fn ap_bases(&self) -> &[EdwardsPoint] {
+        return &self.ap_bases;
+    }
+
+    pub fn threshold(&self) -> usize {
+        return self.threshold;
+    }
+
+    pub fn num_auditors(&self) -> usize {
+        return self.verification_keys.len();
+    }
+
+    //a = sum of r_i*x_i, None if the proof was not made for the ap_bases
+    #[allow(non_snake_case)]
+    fn proof_scalar(&self, kyc_proof: &KYCProof) -> Option<Scalar> {
+        if kyc_proof.r().len() != self.ap_bases.len() {
+            return None;
+        }
+        let mut a = Scalar::zero();
+        for i in 0..self.x.len() {
+            a += kyc_proof.r()[i] * self.x[i];
+        }
+        let Aa = kyc_proof.ap_recoverable_keys().x;
+        let expected: EdwardsPoint = kyc_proof
+            .r()
+            .iter()
+            .zip(self.ap_bases.iter())
+            .map(|(r, base)| r * base)
+            .sum();
+        if a == Scalar::zero() || Aa != expected {
+            return None;
+        }
+        return Some(a);
+    }
+}
+
+impl AuditorShare {
+    pub fn index(&self) -> u64 {
+        return self.index;
+    }
+}
+
+impl PartialDecryption {
+    pub fn index(&self) -> u64 {
+        return self.index;
+    }
+}
+
+//The trusted setup of the auditors, the dealer forgets b and d after handing out the shares.
+//Panics unless 0 < threshold <= num_auditors.
+#[allow(non_snake_case)]
+pub fn deal_threshold_ap_key(
+    num_ap_bases: usize,
+    threshold: usize,
+    num_auditors: usize,
+) -> (ThresholdApKey, Vec<AuditorShare>) {
+    assert!(threshold > 0 && threshold <= num_auditors);
+    let mut csprng: OsRng = OsRng::default();
+    let G = get_G();
+    let b = Scalar::random(&mut csprng);
+    let x: Vec<Scalar> = (0..num_ap_bases)
+        .map(|_| Scalar::random(&mut csprng))
+        .collect();
+    let ap_bases = x.iter().map(|x_i| (b * x_i) * G).collect();
+
+    //f(0) = d
+    let mut coefficients = vec![b.invert()];
+    for _ in 1..threshold {
+        coefficients.push(Scalar::random(&mut csprng));
+    }
+    let mut shares = Vec::new();
+    let mut verification_keys = Vec::new();
+    for j in 1..=num_auditors as u64 {
+        let d = coefficients
+            .iter()
+            .rev()
+            .fold(Scalar::zero(), |sum, c| sum * Scalar::from(j) + c);
+        shares.push(AuditorShare { index: j, d });
+        verification_keys.push(d * G);
+    }
+    return (
+        ThresholdApKey {
+            ap_bases,
+            x,
+            verification_keys,
+            threshold,
+        },
+        shares,
+    );
+}
+
+#[allow(non_snake_case)]
+fn partial_tuples(U: EdwardsPoint, Ba: EdwardsPoint, D: EdwardsPoint) -> Vec<VectorTuple> {
+    return vec![VectorTuple {
+        values: vec![
+            CurveVector { x: get_G(), y: U },
+            CurveVector { x: Ba, y: D },
+        ],
+    }];
+}
+
+#[allow(non_snake_case)]
+pub fn create_partial_decryption(share: &AuditorShare, kyc_proof: &KYCProof) -> PartialDecryption {
+    let Ba = kyc_proof.ap_recoverable_keys().y;
+    let D = share.d * Ba;
+    let tuples = partial_tuples(share.d * get_G(), Ba, D);
+    return PartialDecryption {
+        index: share.index,
+        D,
+        proof: create_zkplmt(&tuples, 0, share.d),
+    };
+}
+
+pub fn verify_partial_decryption(
+    key: &ThresholdApKey,
+    kyc_proof: &KYCProof,
+    partial: &PartialDecryption,
+) -> bool {
+    if partial.index == 0 || partial.index > key.verification_keys.len() as u64 {
+        return false;
+    }
+    let tuples = partial_tuples(
+        key.verification_keys[partial.index as usize - 1],
+        kyc_proof.ap_recoverable_keys().y,
+        partial.D,
+    );
+    return verify_zkplmt(&tuples, &partial.proof);
+}
+
+//The Lagrange coefficient at 0 of the share at index among indices
+fn lagrange_coefficient(index: u64, indices: &[u64]) -> Scalar {
+    let mut numerator = Scalar::one();
+    let mut denominator = Scalar::one();
+    for other in indices {
+        if *other != index {
+            numerator *= Scalar::from(*other);
+            denominator *= Scalar::from(*other) - Scalar::from(index);
+        }
+    }
+    return numerator * denominator.invert();
+}
+
+//Every partial is checked, so a wrong one is reported rather than giving a wrong identity.
+//The first threshold partials are combined.
+#[allow(non_snake_case)]
+pub fn combine_partial_decryptions(
+    key: &ThresholdApKey,
+    kyc_proof: &KYCProof,
+    partials: &[PartialDecryption],
+    registered_identities: &IdentityRegistry,
+) -> Result<IdentityIndex, ThresholdTraceError> {
+    let a = key
+        .proof_scalar(kyc_proof)
+        .ok_or(ThresholdTraceError::ProofNotForKey)?;
+    let mut indices: Vec<u64> = Vec::new();
+    for partial in partials {
+        if indices.contains(&partial.index) {
+            return Err(ThresholdTraceError::DuplicatePartial {
+                index: partial.index,
+            });
+        }
+        if !verify_partial_decryption(key, kyc_proof, partial) {
+            return Err(ThresholdTraceError::InvalidPartial {
+                index: partial.index,
+            });
+        }
+        indices.push(partial.index);
+    }
+    if partials.len() < key.threshold {
+        return Err(ThresholdTraceError::NotEnoughPartials {
+            required: key.threshold,
+            available: partials.len(),
+        });
+    }
+
+    let indices = &indices[..key.threshold];
+    let mut dBa = EdwardsPoint::default();
+    for partial in &partials[..key.threshold] {
+        dBa += lagrange_coefficient(partial.index, indices) * partial.D;
+    }
+    let public_key = a.invert() * dBa;
+    return registered_identities
+        .get(&public_key)
+        .ok_or(ThresholdTraceError::NotRegistered);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_threshold_trace() {
+        let mut csprng: OsRng = OsRng::default();
+        let G = get_G();
+        let secrets: Vec<Scalar> = (0..4).map(|_| Scalar::random(&mut csprng)).collect();
+        let registry =
+            IdentityRegistry::new(&secrets.iter().map(|p| p * G).collect::<Vec<EdwardsPoint>>());
+
+        let (key, shares) = deal_threshold_ap_key(3, 3, 5);
+        assert_eq!(key.num_auditors(), 5);
+        let mut sources: Vec<CurveVector> = (0..4)
+            .map(|_| {
+                let x = get_random_curve_point();
+                CurveVector {
+                    x: x,
+                    y: Scalar::random(&mut csprng) * x,
+                }
+            })
+            .collect();
+        sources[0].y = secrets[1] * sources[0].x;
+        let T = get_random_curve_point();
+        let (_, kyc_proof) = create_kyc(&sources, 0, key.ap_bases(), &T, secrets[1]);
+
+        let partials: Vec<PartialDecryption> = shares
+            .iter()
+            .map(|share| create_partial_decryption(share, &kyc_proof))
+            .collect();
+        for partial in &partials {
+            assert!(verify_partial_decryption(&key, &kyc_proof, partial));
+        }
+
+        //any 3 of the 5 auditors
+        let subsets: [[usize; 3]; 3] = [[0, 1, 2], [0, 2, 4], [4, 3, 1]];
+        for subset in subsets.iter() {
+            let chosen: Vec<PartialDecryption> =
+                subset.iter().map(|j| partials[*j].clone()).collect();
+            assert_eq!(
+                combine_partial_decryptions(&key, &kyc_proof, &chosen, &registry),
+                Ok(1)
+            );
+        }
+
+        assert_eq!(
+            combine_partial_decryptions(&key, &kyc_proof, &partials[..2], &registry),
+            Err(ThresholdTraceError::NotEnoughPartials {
+                required: 3,
+                available: 2
+            })
+        );
+        let duplicated = vec![
+            partials[0].clone(),
+            partials[1].clone(),
+            partials[0].clone(),
+        ];
+        assert_eq!(
+            combine_partial_decryptions(&key, &kyc_proof, &duplicated, &registry),
+            Err(ThresholdTraceError::DuplicatePartial { index: 1 })
+        );
+
+        //a rogue auditor cannot change the result
+        let mut rogue = partials[..3].to_vec();
+        rogue[2].D += G;
+        assert_eq!(
+            combine_partial_decryptions(&key, &kyc_proof, &rogue, &registry),
+            Err(ThresholdTraceError::InvalidPartial { index: 3 })
+        );
+        let forged = create_partial_decryption(
+            &AuditorShare {
+                index: 3,
+                d: Scalar::random(&mut csprng),
+            },
+            &kyc_proof,
+        );
+        assert!(!verify_partial_decryption(&key, &kyc_proof, &forged));
+
+        let (other_key, _) = deal_threshold_ap_key(3, 3, 5);
+        assert_eq!(
+            combine_partial_decryptions(&other_key, &kyc_proof, &partials, &registry),
+            Err(ThresholdTraceError::ProofNotForKey)
+        );
+    }
+}
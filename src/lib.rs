@@ -4,6 +4,8 @@
 
 #[macro_use]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 extern crate rand_core;
 extern crate serde;
 extern crate sha2;
@@ -12,8 +14,17 @@ extern crate bincode;
 
 extern crate curve25519_dalek;
 pub mod bulletproofs;
+pub mod bulletproofs_mpc;
+pub mod bulletproofs_plus;
+pub mod comparison_proof;
+pub mod discrete_log;
 pub mod kyc_proof;
+pub mod kyc_registry;
+pub mod kyc_threshold;
+pub mod r1cs;
+pub mod range_or_proof;
 pub mod range_proof;
+pub mod set_membership;
 pub mod transaction;
 pub mod value_bound_signature;
 pub mod zkplmt_const_size;
@@ -100,6 +111,43 @@ pub mod core {
         d: Vec<Scalar>,
     }
 
+    impl Proof {
+        //The number of tuples the proof is over
+        pub fn num_tuples(&self) -> usize {
+            return self.c.len();
+        }
+
+        pub(crate) fn c(&self) -> &[Scalar] {
+            return &self.c;
+        }
+
+        pub(crate) fn d(&self) -> &[Scalar] {
+            return &self.d;
+        }
+
+        //The c then the d, 64 bytes per tuple
+        pub fn to_bytes(&self) -> Vec<u8> {
+            let mut bytes = Vec::with_capacity(64 * self.c.len());
+            for scalar in self.c.iter().chain(self.d.iter()) {
+                bytes.extend_from_slice(scalar.as_bytes());
+            }
+            return bytes;
+        }
+
+        pub fn from_bytes(bytes: &[u8]) -> Result<Proof, DecodeError> {
+            if bytes.len() % 64 != 0 || bytes.len() == 0 {
+                return Err(DecodeError::WrongLength);
+            }
+            let n = bytes.len() / 64;
+            let mut c = Vec::with_capacity(n);
+            let mut d = Vec::with_capacity(n);
+            for i in 0..n {
+                c.push(read_scalar(&bytes[32 * i..])?);
+                d.push(read_scalar(&bytes[32 * (n + i)..])?);
+            }
+            return Ok(Proof { c: c, d: d });
+        }
+    }
 
     pub fn get_random_curve_point() -> EdwardsPoint {
         let mut inputs = [0u8; 8];
@@ -143,6 +191,41 @@ pub mod core {
         }
     }
 
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum DecodeError {
+        WrongLength,
+        //the scalar is not reduced modulo the group order
+        NonCanonicalScalar,
+        //the point is not a canonical encoding of a point of the prime order subgroup
+        InvalidPoint,
+    }
+
+    //Reads the scalar encoded in the first 32 bytes
+    pub fn read_scalar(bytes: &[u8]) -> Result<Scalar, DecodeError> {
+        if bytes.len() < 32 {
+            return Err(DecodeError::WrongLength);
+        }
+        let mut buf = [0u8; 32];
+        buf.copy_from_slice(&bytes[0..32]);
+        return Scalar::from_canonical_bytes(buf).ok_or(DecodeError::NonCanonicalScalar);
+    }
+
+    //Reads the point encoded in the first 32 bytes
+    pub fn read_point(bytes: &[u8]) -> Result<EdwardsPoint, DecodeError> {
+        if bytes.len() < 32 {
+            return Err(DecodeError::WrongLength);
+        }
+        let mut buf = [0u8; 32];
+        buf.copy_from_slice(&bytes[0..32]);
+        let point = CompressedEdwardsY(buf)
+            .decompress()
+            .ok_or(DecodeError::InvalidPoint)?;
+        if !point.is_torsion_free() || point.compress().to_bytes() != buf {
+            return Err(DecodeError::InvalidPoint);
+        }
+        return Ok(point);
+    }
+
     pub fn copy<T: Copy>(target: &mut [T], source: &[T]) {
         assert!(target.len() >= source.len());
         for i in 0..source.len() {
@@ -212,95 +295,169 @@ pub mod core {
         return create_zkplmt(tuples, hidden_index, secret);
     }
 
-    #[allow(non_snake_case)]
-    pub fn create_zkplmt(tuples: &[VectorTuple], hidden_index: usize, secret: Scalar) -> Proof {
-        assert!(tuples.len() > 0);
+    //The prover state of a zkplmt between its commitment and the challenge
+    pub struct ZkplmtCommitment {
+        r: Scalar,
+        c: Vec<Scalar>,
+        d: Vec<Scalar>,
+        hidden_index: usize,
+    }
 
+    fn tuples_are_well_formed(tuples: &[VectorTuple]) -> bool {
+        if tuples.len() == 0 || tuples[0].values.len() == 0 {
+            return false;
+        }
         let vectors_per_tuple = tuples[0].values.len();
-        assert!(vectors_per_tuple > 0);
+        return tuples.iter().all(|t| t.values.len() == vectors_per_tuple);
+    }
 
+    //The L of every vector followed by the tuples, L(j, i) being the L of vector i of tuple j
+    #[allow(non_snake_case)]
+    pub(crate) fn zkplmt_hash_input<F: Fn(usize, usize) -> EdwardsPoint>(
+        tuples: &[VectorTuple],
+        L: F,
+    ) -> Vec<u8> {
+        let vectors_per_tuple = tuples[0].values.len();
         let size_of_tuples = tuples[0].size() * tuples.len();
         let size_of_Ls = 32 * tuples.len() * vectors_per_tuple * 2;
+        let mut hash_input = vec![0u8; size_of_Ls + size_of_tuples];
+        for j in 0..tuples.len() {
+            for i in 0..vectors_per_tuple {
+                let bytes = L(j, i).compress().to_bytes();
+                let target_index = (j * vectors_per_tuple + i) * bytes.len();
+                copy(&mut hash_input[target_index..], &bytes);
+            }
+        }
+        for i in 0..tuples.len() {
+            tuples[i].fill_bytes(&mut hash_input[size_of_Ls + i * tuples[0].size()..]);
+        }
+        return hash_input;
+    }
+
+    //The first move of create_zkplmt. Returns the prover state and the bytes the challenge
+    //has to be computed from.
+    #[allow(non_snake_case)]
+    pub fn zkplmt_commit(
+        tuples: &[VectorTuple],
+        hidden_index: usize,
+    ) -> (ZkplmtCommitment, Vec<u8>) {
+        assert!(tuples_are_well_formed(tuples));
+        assert!(hidden_index < tuples.len());
+
         let mut csprng: OsRng = OsRng::default();
         let r = Scalar::random(&mut csprng);
         let mut c = vec![Scalar::zero(); tuples.len()];
         let mut d = vec![Scalar::zero(); tuples.len()];
-        let mut sum = Scalar::zero();
-        let mut hash_input = vec![0u8; size_of_Ls + size_of_tuples];
         for j in 0..tuples.len() {
-            assert!(tuples[j].values.len() == vectors_per_tuple);
             if j != hidden_index {
                 c[j] = Scalar::random(&mut csprng);
                 d[j] = Scalar::random(&mut csprng);
-                for i in 0..vectors_per_tuple {
-                    let L = c[j] * tuples[j].values[i].x + d[j] * tuples[j].values[i].y;
-                    let bytes = L.compress().to_bytes();
-                    let target_index = (j * vectors_per_tuple + i) * bytes.len();
-                    copy(&mut hash_input[target_index..], &bytes);
-                }
-                sum += d[j];
             }
         }
-        for i in 0..vectors_per_tuple {
-            let L = r * tuples[hidden_index].values[i].x;
-            let bytes = L.compress().to_bytes();
-            let target_index = (hidden_index * vectors_per_tuple + i) * bytes.len();
-            copy(&mut hash_input[target_index..], &bytes);
-        }
+        let hash_input = zkplmt_hash_input(tuples, |j, i| {
+            let v = &tuples[j].values[i];
+            if j == hidden_index {
+                r * v.x
+            } else {
+                EdwardsPoint::multiscalar_mul(&[c[j], d[j]], &[v.x, v.y])
+            }
+        });
+        return (
+            ZkplmtCommitment {
+                r,
+                c,
+                d,
+                hidden_index,
+            },
+            hash_input,
+        );
+    }
 
-        for i in 0..tuples.len() {
-            tuples[i].fill_bytes(&mut hash_input[size_of_Ls + i * tuples[0].size()..]);
-        }
-        let hash_scalar = Scalar::hash_from_bytes::<Sha512>(&hash_input);
-        d[hidden_index] = hash_scalar - sum;
-        c[hidden_index] = r - d[hidden_index] * secret;
+    //The answer to the challenge, the d of the proof add up to it
+    pub fn zkplmt_respond(
+        commitment: ZkplmtCommitment,
+        secret: Scalar,
+        challenge: Scalar,
+    ) -> Proof {
+        let hidden_index = commitment.hidden_index;
+        let mut c = commitment.c;
+        let mut d = commitment.d;
+        let sum = d.iter().fold(Scalar::zero(), |acc, d| acc + d);
+        d[hidden_index] = challenge - sum;
+        c[hidden_index] = commitment.r - d[hidden_index] * secret;
+        Proof { c: c, d: d }
+    }
 
+    //A proof for a given challenge made without any secret, to be used as a branch of an
+    //OR proof whose challenge it does not control
+    pub fn simulate_zkplmt(tuples: &[VectorTuple], challenge: Scalar) -> Proof {
+        assert!(tuples_are_well_formed(tuples));
+        let mut csprng: OsRng = OsRng::default();
+        let c: Vec<Scalar> = (0..tuples.len())
+            .map(|_| Scalar::random(&mut csprng))
+            .collect();
+        let mut d: Vec<Scalar> = (0..tuples.len())
+            .map(|_| Scalar::random(&mut csprng))
+            .collect();
+        let sum = d[1..].iter().fold(Scalar::zero(), |acc, d| acc + d);
+        d[0] = challenge - sum;
         Proof { c: c, d: d }
     }
 
-    #[allow(non_snake_case)]
-    pub fn verify_zkplmt(tuples: &[VectorTuple], proof: &Proof) -> bool {
-        let vectors_per_tuple = tuples[0].values.len();
-        let mut mult_sc_vec = vec![Scalar::zero(); 2];
-        let mut mult_ed_vec = vec![EdwardsPoint::default(); 2];
-        if vectors_per_tuple == 0 {
-            return false;
+    //The bytes the challenge of the proof was computed from, None if the proof does not fit
+    //the tuples
+    pub fn zkplmt_proof_hash_input(tuples: &[VectorTuple], proof: &Proof) -> Option<Vec<u8>> {
+        if !tuples_are_well_formed(tuples)
+            || proof.c.len() != tuples.len()
+            || proof.d.len() != tuples.len()
+        {
+            return None;
         }
-        let size_of_tuples = tuples[0].size() * tuples.len();
-        let size_of_Ls = 32 * tuples.len() * vectors_per_tuple * 2;
-        let c = &proof.c;
-        let d = &proof.d;
-        let vectors_per_tuple = tuples[0].values.len();
+        return Some(zkplmt_hash_input(tuples, |j, i| {
+            let v = &tuples[j].values[i];
+            EdwardsPoint::multiscalar_mul(&[proof.c[j], proof.d[j]], &[v.x, v.y])
+        }));
+    }
 
-        let mut sum = Scalar::zero();
-        let mut hash_input = vec![0u8; size_of_Ls + size_of_tuples];
+    //The challenge the proof answers
+    pub fn zkplmt_challenge(proof: &Proof) -> Scalar {
+        return proof.d.iter().fold(Scalar::zero(), |acc, d| acc + d);
+    }
 
-        for j in 0..tuples.len() {
-            if tuples[j].values.len() != vectors_per_tuple {
-                return false;
-            }
-            for i in 0..vectors_per_tuple {
-                mult_ed_vec[0] = tuples[j].values[i].x;
-                mult_ed_vec[1] = tuples[j].values[i].y;
-                mult_sc_vec[0] = c[j];
-                mult_sc_vec[1] = d[j];
+    pub fn create_zkplmt(tuples: &[VectorTuple], hidden_index: usize, secret: Scalar) -> Proof {
+        return create_zkplmt_with_message(tuples, hidden_index, secret, &[]);
+    }
 
-                let L = EdwardsPoint::multiscalar_mul(&mult_sc_vec, &mult_ed_vec);
-                let bytes = L.compress().to_bytes();
-                let target_index = (j * vectors_per_tuple + i) * bytes.len();
-                copy(&mut hash_input[target_index..], &bytes);
-            }
-            sum += d[j];
-        }
+    pub fn verify_zkplmt(tuples: &[VectorTuple], proof: &Proof) -> bool {
+        return verify_zkplmt_with_message(tuples, proof, &[]);
+    }
 
-        for i in 0..tuples.len() {
-            tuples[i].fill_bytes(&mut hash_input[size_of_Ls + i * tuples[0].size()..]);
-        }
+    //The message is hashed after the tuples, so the proof does not hold for another message.
+    //An empty message gives the proof of create_zkplmt.
+    pub fn create_zkplmt_with_message(
+        tuples: &[VectorTuple],
+        hidden_index: usize,
+        secret: Scalar,
+        message: &[u8],
+    ) -> Proof {
+        let (commitment, mut hash_input) = zkplmt_commit(tuples, hidden_index);
+        hash_input.extend_from_slice(message);
         let hash_scalar = Scalar::hash_from_bytes::<Sha512>(&hash_input);
-        if hash_scalar.eq(&sum) {
-            return true;
-        } else {
-            return false;
+        return zkplmt_respond(commitment, secret, hash_scalar);
+    }
+
+    pub fn verify_zkplmt_with_message(
+        tuples: &[VectorTuple],
+        proof: &Proof,
+        message: &[u8],
+    ) -> bool {
+        match zkplmt_proof_hash_input(tuples, proof) {
+            Some(mut hash_input) => {
+                hash_input.extend_from_slice(message);
+                let hash_scalar = Scalar::hash_from_bytes::<Sha512>(&hash_input);
+                return hash_scalar == zkplmt_challenge(proof);
+            }
+            None => return false,
         }
     }
 }
@@ -0,0 +1,644 @@
+//Arithmetic circuit proofs (section 5 of the Bulletproofs paper) on top of create_bulletproof.
+//A statement is a set of multiplication gates aL[i]*aR[i] = aO[i] and linear constraints over
+//the gate wires and the committed values V[j] = v[j]*GInit + gamma[j]*HInit. Gadgets are written
+//once against the ConstraintSystem trait and run by both the Prover and the Verifier.
+
+use super::bulletproofs::*;
+use alloc::vec::*;
+use core::ops::{Add, Mul, Neg, Sub};
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::IsIdentity;
+use curve25519_dalek::traits::VartimeMultiscalarMul;
+use rand_core::OsRng;
+use serde::*;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum R1CSError {
+    //the bases do not have enough generators for the number of multipliers
+    InvalidGeneratorsLength,
+    //the prover was asked for a multiplier without its assignment
+    MissingAssignment,
+    VerificationError,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Variable {
+    Committed(usize),
+    MultiplierLeft(usize),
+    MultiplierRight(usize),
+    MultiplierOutput(usize),
+    One,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinearCombination {
+    terms: Vec<(Variable, Scalar)>,
+}
+
+impl From<Variable> for LinearCombination {
+    fn from(v: Variable) -> LinearCombination {
+        LinearCombination {
+            terms: vec![(v, Scalar::one())],
+        }
+    }
+}
+
+impl From<Scalar> for LinearCombination {
+    fn from(s: Scalar) -> LinearCombination {
+        LinearCombination {
+            terms: vec![(Variable::One, s)],
+        }
+    }
+}
+
+impl<L: Into<LinearCombination>> Add<L> for LinearCombination {
+    type Output = LinearCombination;
+    fn add(mut self, rhs: L) -> LinearCombination {
+        self.terms.extend(rhs.into().terms);
+        return self;
+    }
+}
+
+impl<L: Into<LinearCombination>> Sub<L> for LinearCombination {
+    type Output = LinearCombination;
+    fn sub(mut self, rhs: L) -> LinearCombination {
+        self.terms
+            .extend(rhs.into().terms.into_iter().map(|(v, c)| (v, -c)));
+        return self;
+    }
+}
+
+impl Neg for LinearCombination {
+    type Output = LinearCombination;
+    fn neg(self) -> LinearCombination {
+        LinearCombination {
+            terms: self.terms.into_iter().map(|(v, c)| (v, -c)).collect(),
+        }
+    }
+}
+
+impl Mul<Scalar> for LinearCombination {
+    type Output = LinearCombination;
+    fn mul(self, rhs: Scalar) -> LinearCombination {
+        LinearCombination {
+            terms: self.terms.into_iter().map(|(v, c)| (v, c * rhs)).collect(),
+        }
+    }
+}
+
+impl<L: Into<LinearCombination>> Add<L> for Variable {
+    type Output = LinearCombination;
+    fn add(self, rhs: L) -> LinearCombination {
+        return LinearCombination::from(self) + rhs;
+    }
+}
+
+impl<L: Into<LinearCombination>> Sub<L> for Variable {
+    type Output = LinearCombination;
+    fn sub(self, rhs: L) -> LinearCombination {
+        return LinearCombination::from(self) - rhs;
+    }
+}
+
+impl Mul<Scalar> for Variable {
+    type Output = LinearCombination;
+    fn mul(self, rhs: Scalar) -> LinearCombination {
+        LinearCombination {
+            terms: vec![(self, rhs)],
+        }
+    }
+}
+
+pub trait ConstraintSystem {
+    //Adds the gate left*right = out and returns its three wires.
+    fn multiply(
+        &mut self,
+        left: LinearCombination,
+        right: LinearCombination,
+    ) -> (Variable, Variable, Variable);
+
+    //Adds a gate whose inputs are free wires, the prover passes their values.
+    fn allocate_multiplier(
+        &mut self,
+        input_assignments: Option<(Scalar, Scalar)>,
+    ) -> Result<(Variable, Variable, Variable), R1CSError>;
+
+    //Requires lc = 0.
+    fn constrain(&mut self, lc: LinearCombination);
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct R1CSProof {
+    A_I: EdwardsPoint,
+    A_O: EdwardsPoint,
+    S: EdwardsPoint,
+    T1: EdwardsPoint,
+    T3: EdwardsPoint,
+    T4: EdwardsPoint,
+    T5: EdwardsPoint,
+    T6: EdwardsPoint,
+    t_x: Scalar,
+    tao_x: Scalar,
+    mu: Scalar,
+    bullet_proof: BulletProof,
+}
+
+//Folds the constraints with powers of z into
+//<wL,aL> + <wR,aR> + <wO,aO> = <wV,v> + wc
+#[allow(non_snake_case)]
+fn flatten_constraints(
+    constraints: &[LinearCombination],
+    z: Scalar,
+    n: usize,
+    m: usize,
+) -> (Vec<Scalar>, Vec<Scalar>, Vec<Scalar>, Vec<Scalar>, Scalar) {
+    let mut wL = vec![Scalar::zero(); n];
+    let mut wR = vec![Scalar::zero(); n];
+    let mut wO = vec![Scalar::zero(); n];
+    let mut wV = vec![Scalar::zero(); m];
+    let mut wc = Scalar::zero();
+
+    let mut z_q = z;
+    for lc in constraints {
+        for (var, coeff) in &lc.terms {
+            match var {
+                Variable::MultiplierLeft(i) => wL[*i] += z_q * coeff,
+                Variable::MultiplierRight(i) => wR[*i] += z_q * coeff,
+                Variable::MultiplierOutput(i) => wO[*i] += z_q * coeff,
+                Variable::Committed(j) => wV[*j] -= z_q * coeff,
+                Variable::One => wc -= z_q * coeff,
+            }
+        }
+        z_q *= z;
+    }
+    return (wL, wR, wO, wV, wc);
+}
+
+#[allow(non_snake_case)]
+pub struct Prover<'a> {
+    bases: &'a Bases,
+    transcript: Transcript,
+    constraints: Vec<LinearCombination>,
+    v: Vec<Scalar>,
+    gamma: Vec<Scalar>,
+    aL: Vec<Scalar>,
+    aR: Vec<Scalar>,
+    aO: Vec<Scalar>,
+}
+
+impl<'a> Prover<'a> {
+    pub fn new(bases: &'a Bases) -> Prover<'a> {
+        Prover {
+            bases,
+            transcript: Transcript::new(b"r1cs"),
+            constraints: Vec::new(),
+            v: Vec::new(),
+            gamma: Vec::new(),
+            aL: Vec::new(),
+            aR: Vec::new(),
+            aO: Vec::new(),
+        }
+    }
+
+    //Commits to v with blinding gamma, the verifier has to be given the commitments in the
+    //same order.
+    #[allow(non_snake_case)]
+    pub fn commit(&mut self, v: Scalar, gamma: Scalar) -> (EdwardsPoint, Variable) {
+        let V = v * self.bases.GInit + gamma * self.bases.HInit;
+        self.transcript.append_point(&V);
+        self.v.push(v);
+        self.gamma.push(gamma);
+        return (V, Variable::Committed(self.v.len() - 1));
+    }
+
+    fn eval(&self, lc: &LinearCombination) -> Scalar {
+        let mut sum = Scalar::zero();
+        for (var, coeff) in &lc.terms {
+            sum += coeff
+                * match var {
+                    Variable::Committed(j) => self.v[*j],
+                    Variable::MultiplierLeft(i) => self.aL[*i],
+                    Variable::MultiplierRight(i) => self.aR[*i],
+                    Variable::MultiplierOutput(i) => self.aO[*i],
+                    Variable::One => Scalar::one(),
+                };
+        }
+        return sum;
+    }
+
+    #[allow(non_snake_case)]
+    pub fn prove(mut self) -> Result<R1CSProof, R1CSError> {
+        let bases = self.bases;
+        let m = self.v.len();
+        //the gates are padded with 0*0 = 0 up to a power of 2
+        let n = to_2s_power(self.aL.len());
+        if bases.num_generators() < n {
+            return Err(R1CSError::InvalidGeneratorsLength);
+        }
+        let gs = &bases.Gs[0..n];
+        let hs = &bases.Hs[0..n];
+        let g = bases.GInit;
+        let h = bases.HInit;
+        self.aL.resize(n, Scalar::zero());
+        self.aR.resize(n, Scalar::zero());
+        self.aO.resize(n, Scalar::zero());
+        self.transcript.append_u64(m as u64);
+        self.transcript.append_u64(n as u64);
+
+        let mut csprng: OsRng = OsRng::default();
+        let alpha = Scalar::random(&mut csprng);
+        let beta = Scalar::random(&mut csprng);
+        let rho = Scalar::random(&mut csprng);
+        let mut sL = vec![Scalar::zero(); n];
+        let mut sR = vec![Scalar::zero(); n];
+        fill_random_scalars(&mut sL);
+        fill_random_scalars(&mut sR);
+
+        let A_I = alpha * h + multiscalar_mul_add(&self.aL, gs, &self.aR, hs);
+        let A_O = beta * h + multiscalar_mul_add(&self.aO, gs, &[], &[]);
+        let S = rho * h + multiscalar_mul_add(&sL, gs, &sR, hs);
+        self.transcript.append_point(&A_I);
+        self.transcript.append_point(&A_O);
+        self.transcript.append_point(&S);
+        let y = self.transcript.challenge();
+        let z = self.transcript.challenge();
+
+        let (wL, wR, wO, wV, _) = flatten_constraints(&self.constraints, z, n, m);
+        let yn = to_the_n(y, n);
+        let y_n = to_the_n(y.invert(), n);
+
+        //l(X) = l1*X + l2*X^2 + l3*X^3, r(X) = r0 + r1*X + r3*X^3
+        let l1 = add_scalar_arrays(&self.aL, &multiply_scalar_arrays(&y_n, &wR));
+        let l2 = self.aO.clone();
+        let l3 = sL;
+        let r0 = substract_scalar_arrays(&wO, &yn);
+        let r1 = add_scalar_arrays(&multiply_scalar_arrays(&yn, &self.aR), &wL);
+        let r3 = multiply_scalar_arrays(&yn, &sR);
+
+        //t2 is the constraint value, the other coefficients are committed
+        let t1 = inner_product(&l1, &r0);
+        let t3 = inner_product(&l2, &r1) + inner_product(&l3, &r0);
+        let t4 = inner_product(&l1, &r3) + inner_product(&l3, &r1);
+        let t5 = inner_product(&l2, &r3);
+        let t6 = inner_product(&l3, &r3);
+
+        let tao1 = Scalar::random(&mut csprng);
+        let tao3 = Scalar::random(&mut csprng);
+        let tao4 = Scalar::random(&mut csprng);
+        let tao5 = Scalar::random(&mut csprng);
+        let tao6 = Scalar::random(&mut csprng);
+        let T1 = t1 * g + tao1 * h;
+        let T3 = t3 * g + tao3 * h;
+        let T4 = t4 * g + tao4 * h;
+        let T5 = t5 * g + tao5 * h;
+        let T6 = t6 * g + tao6 * h;
+        for T in [T1, T3, T4, T5, T6].iter() {
+            self.transcript.append_point(T);
+        }
+        let x = self.transcript.challenge();
+
+        let x2 = x * x;
+        let x3 = x2 * x;
+        let l = add_scalar_arrays(
+            &add_scalar_arrays(
+                &multiply_scalar_to_array(&l1, x),
+                &multiply_scalar_to_array(&l2, x2),
+            ),
+            &multiply_scalar_to_array(&l3, x3),
+        );
+        let r = add_scalar_arrays(
+            &add_scalar_arrays(&r0, &multiply_scalar_to_array(&r1, x)),
+            &multiply_scalar_to_array(&r3, x3),
+        );
+        let t_x = inner_product(&l, &r);
+        let tao_x = tao1 * x
+            + x2 * inner_product(&wV, &self.gamma)
+            + tao3 * x3
+            + tao4 * x3 * x
+            + tao5 * x3 * x2
+            + tao6 * x3 * x3;
+        let mu = alpha * x + beta * x2 + rho * x3;
+
+        self.transcript.append_scalar(&t_x);
+        self.transcript.append_scalar(&tao_x);
+        self.transcript.append_scalar(&mu);
+        let w = self.transcript.challenge();
+
+        let h_ = mult_ed(&y_n, hs);
+        let bullet_proof = create_bulletproof(n, gs, &h_, w * g, &l, &r);
+
+        return Ok(R1CSProof {
+            A_I,
+            A_O,
+            S,
+            T1,
+            T3,
+            T4,
+            T5,
+            T6,
+            t_x,
+            tao_x,
+            mu,
+            bullet_proof,
+        });
+    }
+}
+
+impl<'a> ConstraintSystem for Prover<'a> {
+    fn multiply(
+        &mut self,
+        left: LinearCombination,
+        right: LinearCombination,
+    ) -> (Variable, Variable, Variable) {
+        let l = self.eval(&left);
+        let r = self.eval(&right);
+        let i = self.aL.len();
+        self.aL.push(l);
+        self.aR.push(r);
+        self.aO.push(l * r);
+        let vars = (
+            Variable::MultiplierLeft(i),
+            Variable::MultiplierRight(i),
+            Variable::MultiplierOutput(i),
+        );
+        self.constrain(left - vars.0);
+        self.constrain(right - vars.1);
+        return vars;
+    }
+
+    fn allocate_multiplier(
+        &mut self,
+        input_assignments: Option<(Scalar, Scalar)>,
+    ) -> Result<(Variable, Variable, Variable), R1CSError> {
+        let (l, r) = input_assignments.ok_or(R1CSError::MissingAssignment)?;
+        let i = self.aL.len();
+        self.aL.push(l);
+        self.aR.push(r);
+        self.aO.push(l * r);
+        return Ok((
+            Variable::MultiplierLeft(i),
+            Variable::MultiplierRight(i),
+            Variable::MultiplierOutput(i),
+        ));
+    }
+
+    fn constrain(&mut self, lc: LinearCombination) {
+        self.constraints.push(lc);
+    }
+}
+
+#[allow(non_snake_case)]
+pub struct Verifier<'a> {
+    bases: &'a Bases,
+    transcript: Transcript,
+    constraints: Vec<LinearCombination>,
+    V: Vec<EdwardsPoint>,
+    num_multipliers: usize,
+}
+
+impl<'a> Verifier<'a> {
+    pub fn new(bases: &'a Bases) -> Verifier<'a> {
+        Verifier {
+            bases,
+            transcript: Transcript::new(b"r1cs"),
+            constraints: Vec::new(),
+            V: Vec::new(),
+            num_multipliers: 0,
+        }
+    }
+
+    #[allow(non_snake_case)]
+    pub fn commit(&mut self, V: EdwardsPoint) -> Variable {
+        self.transcript.append_point(&V);
+        self.V.push(V);
+        return Variable::Committed(self.V.len() - 1);
+    }
+
+    #[allow(non_snake_case)]
+    pub fn verify(mut self, proof: &R1CSProof) -> Result<(), R1CSError> {
+        let bases = self.bases;
+        let m = self.V.len();
+        let n = to_2s_power(self.num_multipliers);
+        if bases.num_generators() < n {
+            return Err(R1CSError::InvalidGeneratorsLength);
+        }
+        let gs = &bases.Gs[0..n];
+        let hs = &bases.Hs[0..n];
+        let g = bases.GInit;
+        let h = bases.HInit;
+        self.transcript.append_u64(m as u64);
+        self.transcript.append_u64(n as u64);
+
+        self.transcript.append_point(&proof.A_I);
+        self.transcript.append_point(&proof.A_O);
+        self.transcript.append_point(&proof.S);
+        let y = self.transcript.challenge();
+        let z = self.transcript.challenge();
+        for T in [proof.T1, proof.T3, proof.T4, proof.T5, proof.T6].iter() {
+            self.transcript.append_point(T);
+        }
+        let x = self.transcript.challenge();
+        self.transcript.append_scalar(&proof.t_x);
+        self.transcript.append_scalar(&proof.tao_x);
+        self.transcript.append_scalar(&proof.mu);
+        let w = self.transcript.challenge();
+
+        let (wL, wR, wO, wV, wc) = flatten_constraints(&self.constraints, z, n, m);
+        let y_n = to_the_n(y.invert(), n);
+        let delta = inner_product(&multiply_scalar_arrays(&y_n, &wR), &wL);
+
+        //t_x*g + tao_x*h = x^2*(<wV,V> + (wc+delta)*g) + sum(x^i*T_i)
+        let x2 = x * x;
+        let x3 = x2 * x;
+        let scalars = vec![
+            proof.t_x - x2 * (wc + delta),
+            proof.tao_x,
+            -x,
+            -x3,
+            -x3 * x,
+            -x3 * x2,
+            -x3 * x3,
+        ]
+        .into_iter()
+        .chain(wV.iter().map(|w| -x2 * w));
+        let points = vec![g, h, proof.T1, proof.T3, proof.T4, proof.T5, proof.T6]
+            .into_iter()
+            .chain(self.V.iter().map(|V| *V))
+            .collect::<Vec<EdwardsPoint>>();
+        if !EdwardsPoint::vartime_multiscalar_mul(scalars, points).is_identity() {
+            return Err(R1CSError::VerificationError);
+        }
+
+        //P = x*A_I + x^2*A_O + x^3*S - mu*h + <x*y^-n*wR,G> + <y^-n*(x*wL + wO) - 1,H>
+        let scalars = vec![x, x2, x3, -proof.mu]
+            .into_iter()
+            .chain(y_n.iter().zip(wR.iter()).map(|(y, w)| x * y * w))
+            .chain((0..n).map(|i| y_n[i] * (x * wL[i] + wO[i]) - Scalar::one()));
+        let points = vec![proof.A_I, proof.A_O, proof.S, h]
+            .into_iter()
+            .chain(gs.iter().map(|p| *p))
+            .chain(hs.iter().map(|p| *p))
+            .collect::<Vec<EdwardsPoint>>();
+        let P = EdwardsPoint::vartime_multiscalar_mul(scalars, points);
+
+        let Q = w * g;
+        if !verify_bulletproof_hmul(n, gs, hs, &y_n, Q, P + proof.t_x * Q, &proof.bullet_proof) {
+            return Err(R1CSError::VerificationError);
+        }
+        return Ok(());
+    }
+}
+
+impl<'a> ConstraintSystem for Verifier<'a> {
+    fn multiply(
+        &mut self,
+        left: LinearCombination,
+        right: LinearCombination,
+    ) -> (Variable, Variable, Variable) {
+        let i = self.num_multipliers;
+        self.num_multipliers += 1;
+        let vars = (
+            Variable::MultiplierLeft(i),
+            Variable::MultiplierRight(i),
+            Variable::MultiplierOutput(i),
+        );
+        self.constrain(left - vars.0);
+        self.constrain(right - vars.1);
+        return vars;
+    }
+
+    fn allocate_multiplier(
+        &mut self,
+        _: Option<(Scalar, Scalar)>,
+    ) -> Result<(Variable, Variable, Variable), R1CSError> {
+        let i = self.num_multipliers;
+        self.num_multipliers += 1;
+        return Ok((
+            Variable::MultiplierLeft(i),
+            Variable::MultiplierRight(i),
+            Variable::MultiplierOutput(i),
+        ));
+    }
+
+    fn constrain(&mut self, lc: LinearCombination) {
+        self.constraints.push(lc);
+    }
+}
+
+//Constrains v to be in [0, 2^n_bits) with one multiplier per bit, the prover passes the value.
+pub fn range_gadget<CS: ConstraintSystem>(
+    cs: &mut CS,
+    v: LinearCombination,
+    value: Option<u64>,
+    n_bits: usize,
+) -> Result<(), R1CSError> {
+    let mut sum = LinearCombination::from(Scalar::zero());
+    let mut exp_2 = Scalar::one();
+    for i in 0..n_bits {
+        //b*(1-b) = 0 with a = b and a - 1 + (1-b) = 0
+        let assignment = value.map(|v| {
+            let bit = (v >> i) & 1;
+            (Scalar::from(bit), Scalar::from(1 - bit))
+        });
+        let (a, b, o) = cs.allocate_multiplier(assignment)?;
+        cs.constrain(o.into());
+        cs.constrain(a + b - Scalar::one());
+        sum = sum + a * exp_2;
+        exp_2 = exp_2 + exp_2;
+    }
+    cs.constrain(sum - v);
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::*;
+
+    #[allow(non_snake_case)]
+    fn exchange_proof(
+        bases: &Bases,
+        v_in: u64,
+        v_out: u64,
+        rate: u64,
+    ) -> (EdwardsPoint, EdwardsPoint, R1CSProof) {
+        let mut csprng: OsRng = OsRng::default();
+        let mut prover = Prover::new(bases);
+        let (V_in, var_in) = prover.commit(Scalar::from(v_in), Scalar::random(&mut csprng));
+        let (V_out, var_out) = prover.commit(Scalar::from(v_out), Scalar::random(&mut csprng));
+        prover.constrain(var_out - var_in * Scalar::from(rate));
+        return (V_in, V_out, prover.prove().unwrap());
+    }
+
+    #[allow(non_snake_case)]
+    fn verify_exchange(
+        bases: &Bases,
+        V_in: EdwardsPoint,
+        V_out: EdwardsPoint,
+        rate: u64,
+        proof: &R1CSProof,
+    ) -> Result<(), R1CSError> {
+        let mut verifier = Verifier::new(bases);
+        let var_in = verifier.commit(V_in);
+        let var_out = verifier.commit(V_out);
+        verifier.constrain(var_out - var_in * Scalar::from(rate));
+        return verifier.verify(proof);
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_r1cs_exchange_rate() {
+        let bases = Bases::new(get_L(), get_K(), 1);
+        let (V_in, V_out, proof) = exchange_proof(&bases, 120, 360, 3);
+        assert_eq!(verify_exchange(&bases, V_in, V_out, 3, &proof), Ok(()));
+        assert!(verify_exchange(&bases, V_in, V_out, 4, &proof).is_err());
+
+        let (V_in, V_out, proof) = exchange_proof(&bases, 120, 361, 3);
+        assert!(verify_exchange(&bases, V_in, V_out, 3, &proof).is_err());
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_r1cs_sum_below_limit() {
+        let bases = Bases::new(get_L(), get_K(), 1);
+        let limit = 1000u64;
+        let prove = |a: u64, b: u64| {
+            let mut csprng: OsRng = OsRng::default();
+            let mut prover = Prover::new(&bases);
+            let (V_a, var_a) = prover.commit(Scalar::from(a), Scalar::random(&mut csprng));
+            let (V_b, var_b) = prover.commit(Scalar::from(b), Scalar::random(&mut csprng));
+            //limit - a - b is a 32 bit number, the prover assigns it modulo 2^64
+            let slack = limit.wrapping_sub(a).wrapping_sub(b);
+            range_gadget(
+                &mut prover,
+                LinearCombination::from(Scalar::from(limit)) - var_a - var_b,
+                Some(slack),
+                32,
+            )
+            .unwrap();
+            (V_a, V_b, prover.prove().unwrap())
+        };
+        let verify = |V_a: EdwardsPoint, V_b: EdwardsPoint, proof: &R1CSProof| {
+            let mut verifier = Verifier::new(&bases);
+            let var_a = verifier.commit(V_a);
+            let var_b = verifier.commit(V_b);
+            range_gadget(
+                &mut verifier,
+                LinearCombination::from(Scalar::from(limit)) - var_a - var_b,
+                None,
+                32,
+            )
+            .unwrap();
+            verifier.verify(proof)
+        };
+
+        let (V_a, V_b, proof) = prove(600, 400);
+        assert_eq!(verify(V_a, V_b, &proof), Ok(()));
+        assert!(verify(V_b, V_a + get_L(), &proof).is_err());
+
+        let (V_a, V_b, proof) = prove(600, 401);
+        assert!(verify(V_a, V_b, &proof).is_err());
+    }
+}
@@ -0,0 +1,400 @@
+//A range proof OR any other statement, e.g. "the value is in range OR the auditor co-signed".
+//Both branches commit, the challenge e of the OR proof is a hash of the message and both
+//commitments, and the branches answer challenges adding up to e. The prover knows a witness
+//for one branch and simulates the other one for a challenge chosen in advance.
+//
+//A branch is anything implementing OrBranch, currently a zkplmt Proof and a BulletRangeProof.
+
+use super::bulletproofs::*;
+use super::core::*;
+use alloc::vec::*;
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::scalar::Scalar;
+use rand_core::OsRng;
+use rand_core::RngCore;
+use serde::*;
+use sha2::Sha512;
+
+pub trait OrBranch: Sized {
+    type Statement;
+    type Witness;
+    type ProverState;
+
+    //The first move of the proof and the bytes the challenge is computed from, Err if the
+    //bases cannot hold the statement
+    fn commit(
+        bases: &Bases,
+        statement: &Self::Statement,
+        witness: &Self::Witness,
+    ) -> Result<(Self::ProverState, Vec<u8>), BasesError>;
+
+    fn respond(bases: &Bases, state: Self::ProverState, challenge: Scalar) -> Self;
+
+    //A proof for the challenge made without a witness, Err if the bases cannot hold the
+    //statement
+    fn simulate(
+        bases: &Bases,
+        statement: &Self::Statement,
+        challenge: Scalar,
+    ) -> Result<Self, BasesError>;
+
+    //The bytes commit returned, recomputed from the proof. None if the proof does not fit
+    //the statement.
+    fn commitment(&self, bases: &Bases, statement: &Self::Statement) -> Option<Vec<u8>>;
+
+    fn verify(&self, bases: &Bases, statement: &Self::Statement, challenge: Scalar) -> bool;
+}
+
+//The tuples, the prover knows the secret of tuple hidden_index
+impl OrBranch for Proof {
+    type Statement = Vec<VectorTuple>;
+    type Witness = (usize, Scalar);
+    type ProverState = (ZkplmtCommitment, Scalar);
+
+    fn commit(
+        _: &Bases,
+        tuples: &Vec<VectorTuple>,
+        witness: &(usize, Scalar),
+    ) -> Result<((ZkplmtCommitment, Scalar), Vec<u8>), BasesError> {
+        let (commitment, hash_input) = zkplmt_commit(tuples, witness.0);
+        return Ok(((commitment, witness.1), hash_input));
+    }
+
+    fn respond(_: &Bases, state: (ZkplmtCommitment, Scalar), challenge: Scalar) -> Proof {
+        return zkplmt_respond(state.0, state.1, challenge);
+    }
+
+    fn simulate(
+        _: &Bases,
+        tuples: &Vec<VectorTuple>,
+        challenge: Scalar,
+    ) -> Result<Proof, BasesError> {
+        return Ok(simulate_zkplmt(tuples, challenge));
+    }
+
+    fn commitment(&self, _: &Bases, tuples: &Vec<VectorTuple>) -> Option<Vec<u8>> {
+        return zkplmt_proof_hash_input(tuples, self);
+    }
+
+    fn verify(&self, _: &Bases, tuples: &Vec<VectorTuple>, challenge: Scalar) -> bool {
+        return zkplmt_proof_hash_input(tuples, self).is_some()
+            && zkplmt_challenge(self) == challenge;
+    }
+}
+
+pub struct RangeProverState {
+    committed: RangeProofCommitment,
+}
+
+//The commitment V of a single value, the witness is the value and its blinding
+impl OrBranch for BulletRangeProof {
+    type Statement = EdwardsPoint;
+    type Witness = (u64, Scalar);
+    type ProverState = RangeProverState;
+
+    #[allow(non_snake_case)]
+    fn commit(
+        bases: &Bases,
+        V: &EdwardsPoint,
+        witness: &(u64, Scalar),
+    ) -> Result<(RangeProverState, Vec<u8>), BasesError> {
+        let committed = bullet_range_proof_ex_T1_T2(
+            &[witness.1],
+            &[witness.0],
+            bases,
+            RangeProofNonces::random(1),
+        )?;
+        let (T1, T2, .., A, SS, _, _, _) = &committed;
+        let bytes = range_commitment_bytes(A, SS, T1, T2, V);
+        return Ok((RangeProverState { committed }, bytes));
+    }
+
+    #[allow(non_snake_case)]
+    fn respond(bases: &Bases, state: RangeProverState, challenge: Scalar) -> BulletRangeProof {
+        let (T1, T2, l0, r0, l1, r1, tao1, tao2, z, m, alpha, rho, gs, hs, A, SS, y, v, gamma) =
+            state.committed;
+        return bullet_range_proof_rest(
+            bases, T1, T2, l0, r0, l1, r1, tao1, tao2, z, m, alpha, rho, gs, hs, A, SS, y, v,
+            &gamma, 1, challenge,
+        );
+    }
+
+    //A proof of a random value V_ in range turned into a proof for V by moving T1 by
+    //z^2*x^-1*(V_ - V), which the verification equation for the challenge x cannot tell.
+    #[allow(non_snake_case)]
+    fn simulate(
+        bases: &Bases,
+        V: &EdwardsPoint,
+        challenge: Scalar,
+    ) -> Result<BulletRangeProof, BasesError> {
+        let mut csprng: OsRng = OsRng::default();
+        let v_ = csprng.next_u64();
+        let gamma_ = Scalar::random(&mut csprng);
+        let V_ = Scalar::from(v_) * bases.GInit + gamma_ * bases.HInit;
+        let (T1_, T2, l0, r0, l1, r1, tao1, tao2, z, m, alpha, rho, gs, hs, A, SS, y, v, gamma) =
+            bullet_range_proof_ex_T1_T2(&[gamma_], &[v_], bases, RangeProofNonces::random(1))?;
+        let T1 = T1_ + z * z * challenge.invert() * (V_ - V);
+        let mut proof = bullet_range_proof_rest(
+            bases, T1, T2, l0, r0, l1, r1, tao1, tao2, z, m, alpha, rho, gs, hs, A, SS, y, v,
+            &gamma, 1, challenge,
+        );
+        proof.V[0] = *V;
+        return Ok(proof);
+    }
+
+    #[allow(non_snake_case)]
+    fn commitment(&self, _: &Bases, V: &EdwardsPoint) -> Option<Vec<u8>> {
+        if self.V.len() != 1 || self.V[0] != *V {
+            return None;
+        }
+        return Some(range_commitment_bytes(
+            &self.A, &self.SS, &self.T1, &self.T2, V,
+        ));
+    }
+
+    #[allow(non_snake_case)]
+    fn verify(&self, bases: &Bases, V: &EdwardsPoint, challenge: Scalar) -> bool {
+        return self.V.len() == 1
+            && self.V[0] == *V
+            && bullet_range_verify_challenge(self, bases, challenge);
+    }
+}
+
+#[allow(non_snake_case)]
+fn range_commitment_bytes(
+    A: &EdwardsPoint,
+    SS: &EdwardsPoint,
+    T1: &EdwardsPoint,
+    T2: &EdwardsPoint,
+    V: &EdwardsPoint,
+) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(5 * 32);
+    for point in [A, SS, T1, T2, V].iter() {
+        bytes.extend_from_slice(&point.compress().to_bytes());
+    }
+    return bytes;
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RangeOrProof<S> {
+    range_proof: BulletRangeProof,
+    other: S,
+    range_challenge: Scalar,
+}
+
+fn or_challenge(message: &[u8], range_commitment: &[u8], other_commitment: &[u8]) -> Scalar {
+    let mut hash_input = Vec::new();
+    hash_input.extend_from_slice(b"range or proof");
+    for part in [message, range_commitment, other_commitment].iter() {
+        hash_input.extend_from_slice(&(part.len() as u64).to_le_bytes());
+        hash_input.extend_from_slice(part);
+    }
+    return Scalar::hash_from_bytes::<Sha512>(&hash_input);
+}
+
+//Proves that V = v*GInit + gamma*HInit with v in range, the other branch is simulated.
+//Err if the bases cannot hold a value or the other statement.
+#[allow(non_snake_case)]
+pub fn create_range_or_proof_fake_other<S: OrBranch>(
+    bases: &Bases,
+    v: u64,
+    gamma: Scalar,
+    other_statement: &S::Statement,
+    message: &[u8],
+) -> Result<RangeOrProof<S>, BasesError> {
+    let mut csprng: OsRng = OsRng::default();
+    let other_challenge = Scalar::random(&mut csprng);
+    let other = S::simulate(bases, other_statement, other_challenge)?;
+    let other_commitment = other.commitment(bases, other_statement).unwrap();
+
+    let V = Scalar::from(v) * bases.GInit + gamma * bases.HInit;
+    let (state, range_commitment) = BulletRangeProof::commit(bases, &V, &(v, gamma))?;
+    let e = or_challenge(message, &range_commitment, &other_commitment);
+    let range_challenge = e - other_challenge;
+    return Ok(RangeOrProof {
+        range_proof: BulletRangeProof::respond(bases, state, range_challenge),
+        other,
+        range_challenge,
+    });
+}
+
+//Proves the other statement, the range branch for V is simulated so v can be anything.
+//Err if the bases cannot hold a value or the other statement.
+#[allow(non_snake_case)]
+pub fn create_range_or_proof_fake_range<S: OrBranch>(
+    bases: &Bases,
+    V: &EdwardsPoint,
+    other_statement: &S::Statement,
+    other_witness: &S::Witness,
+    message: &[u8],
+) -> Result<RangeOrProof<S>, BasesError> {
+    let mut csprng: OsRng = OsRng::default();
+    let range_challenge = Scalar::random(&mut csprng);
+    let range_proof = BulletRangeProof::simulate(bases, V, range_challenge)?;
+    let range_commitment = range_proof.commitment(bases, V).unwrap();
+
+    let (state, other_commitment) = S::commit(bases, other_statement, other_witness)?;
+    let e = or_challenge(message, &range_commitment, &other_commitment);
+    return Ok(RangeOrProof {
+        range_proof,
+        other: S::respond(bases, state, e - range_challenge),
+        range_challenge,
+    });
+}
+
+#[allow(non_snake_case)]
+pub fn verify_range_or_proof<S: OrBranch>(
+    bases: &Bases,
+    V: &EdwardsPoint,
+    other_statement: &S::Statement,
+    proof: &RangeOrProof<S>,
+    message: &[u8],
+) -> bool {
+    let range_commitment = match proof.range_proof.commitment(bases, V) {
+        Some(bytes) => bytes,
+        None => return false,
+    };
+    let other_commitment = match proof.other.commitment(bases, other_statement) {
+        Some(bytes) => bytes,
+        None => return false,
+    };
+    let e = or_challenge(message, &range_commitment, &other_commitment);
+    return proof.range_proof.verify(bases, V, proof.range_challenge)
+        && proof
+            .other
+            .verify(bases, other_statement, e - proof.range_challenge);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //knowledge of the auditor private key as a single tuple zkplmt
+    #[allow(non_snake_case)]
+    fn auditor_statement(bases: &Bases, AP_key: &EdwardsPoint) -> Vec<VectorTuple> {
+        return vec![VectorTuple {
+            values: vec![CurveVector {
+                x: bases.GInit,
+                y: *AP_key,
+            }],
+        }];
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_range_or_zkplmt() {
+        let bases = Bases::new(get_L(), get_K(), 1);
+        let mut csprng: OsRng = OsRng::default();
+        let AP_pr_key = Scalar::random(&mut csprng);
+        let AP_key = AP_pr_key * bases.GInit;
+        let statement = auditor_statement(&bases, &AP_key);
+        let gamma = Scalar::random(&mut csprng);
+
+        //in range
+        let v = csprng.next_u64();
+        let V = Scalar::from(v) * bases.GInit + gamma * bases.HInit;
+        let proof: RangeOrProof<Proof> =
+            create_range_or_proof_fake_other(&bases, v, gamma, &statement, b"message").unwrap();
+        assert!(verify_range_or_proof(
+            &bases, &V, &statement, &proof, b"message"
+        ));
+        assert!(!verify_range_or_proof(
+            &bases,
+            &V,
+            &statement,
+            &proof,
+            b"other message"
+        ));
+        assert!(!verify_range_or_proof(
+            &bases,
+            &(V + bases.GInit),
+            &statement,
+            &proof,
+            b"message"
+        ));
+
+        //out of range, the auditor signs instead
+        let V = -Scalar::one() * bases.GInit + gamma * bases.HInit;
+        let proof: RangeOrProof<Proof> =
+            create_range_or_proof_fake_range(&bases, &V, &statement, &(0, AP_pr_key), b"message")
+                .unwrap();
+        assert!(verify_range_or_proof(
+            &bases, &V, &statement, &proof, b"message"
+        ));
+        let other_key = Scalar::random(&mut csprng) * bases.GInit;
+        assert!(!verify_range_or_proof(
+            &bases,
+            &V,
+            &auditor_statement(&bases, &other_key),
+            &proof,
+            b"message"
+        ));
+
+        let bytes = bincode::serialize(&proof).unwrap();
+        let decoded: RangeOrProof<Proof> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded, proof);
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_range_or_range() {
+        let bases = Bases::new(get_L(), get_K(), 1);
+        let mut csprng: OsRng = OsRng::default();
+        let gamma = Scalar::random(&mut csprng);
+        let escrow_gamma = Scalar::random(&mut csprng);
+        let escrow_v = csprng.next_u64();
+        let escrow_V = Scalar::from(escrow_v) * bases.GInit + escrow_gamma * bases.HInit;
+
+        //V is out of range but the escrow commitment is in range
+        let V = -Scalar::one() * bases.GInit + gamma * bases.HInit;
+        let proof: RangeOrProof<BulletRangeProof> = create_range_or_proof_fake_range(
+            &bases,
+            &V,
+            &escrow_V,
+            &(escrow_v, escrow_gamma),
+            b"escrow",
+        ).unwrap();
+        assert!(verify_range_or_proof(
+            &bases, &V, &escrow_V, &proof, b"escrow"
+        ));
+        assert!(!verify_range_or_proof(
+            &bases, &escrow_V, &V, &proof, b"escrow"
+        ));
+    }
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_range_branch_bases_capacity() {
+        let mut bases = Bases::new(get_L(), get_K(), 1);
+        bases.Gs.truncate(RANGE_SIZE - 1);
+        let V = bases.GInit + bases.HInit;
+        let error = BasesError::InsufficientCapacity {
+            required: 1,
+            available: 0,
+        };
+        assert_eq!(
+            BulletRangeProof::commit(&bases, &V, &(1, Scalar::one())).err(),
+            Some(error)
+        );
+        assert_eq!(
+            BulletRangeProof::simulate(&bases, &V, Scalar::one()).err(),
+            Some(error)
+        );
+        let statement = auditor_statement(&bases, &bases.HInit);
+        assert_eq!(
+            create_range_or_proof_fake_other::<Proof>(&bases, 1, Scalar::one(), &statement, b"")
+                .err(),
+            Some(error)
+        );
+        assert_eq!(
+            create_range_or_proof_fake_range::<BulletRangeProof>(
+                &bases,
+                &V,
+                &V,
+                &(1, Scalar::one()),
+                b""
+            )
+            .err(),
+            Some(error)
+        );
+    }
+}
@@ -4,18 +4,63 @@ extern crate sha2;
 
 extern crate curve25519_dalek;
 
+use super::bulletproofs::Bases;
 use super::core::*;
 use super::value_bound_signature::*;
 use alloc::vec::*;
 use curve25519_dalek::edwards::EdwardsPoint;
 use curve25519_dalek::scalar::Scalar;
-
+use curve25519_dalek::traits::MultiscalarMul;
+use rand_core::OsRng;
+use serde::*;
+use sha2::Sha512;
+
+//v is split into digits of base 2^b, digit i being proved by a ring over the values it can
+//take. With R_i the bases, which add up to zero, and S = s*G, the member of value a of digit
+//i is (G, S), (R_i + a*K, X_i) and the prover knows s such that X_i = s*(R_i + a*K). The X
+//add up to s*v*K = V. Base 2 gives the two member rings of the original proof.
+//
+//A digit of base k costs the point X_i and a Proof of 2k scalars for log2(k) bits, so per bit
+//base 2 takes 5 elements, base 4 takes 4.5, base 8 takes 17/3 and base 16 takes 8.25. Only
+//base 4 gives a smaller proof, for 64 bits 288 elements instead of 320, at the price of twice
+//the ring members to create and verify per bit. Larger bases are not supported.
 #[allow(non_snake_case)]
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct RangeProof {
     proofs: Vec<Proof>,
     X: Vec<EdwardsPoint>,
     S: EdwardsPoint,
+    base: u64,
+}
+
+impl RangeProof {
+    //The length of bincode::serialize for a proof of v < 2^n, None if base or n is not supported
+    pub fn serialized_size(n: usize, base: u64) -> Option<usize> {
+        let bits = check_range(n, base).ok()?;
+        let digit_values = power_of_2_digit_values(n, bits);
+        //a length before each of proofs, X, c and d, 32 bytes per point or scalar and 8 for base
+        let proofs: usize = digit_values
+            .iter()
+            .map(|values| 2 * (8 + 32 * values.len()))
+            .sum();
+        return Some(8 + proofs + 8 + 32 * digit_values.len() + 32 + 8);
+    }
+
+    //s*G for the s of V = v*s*K
+    #[allow(non_snake_case)]
+    pub fn S(&self) -> EdwardsPoint {
+        return self.S;
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RangeProofInputError {
+    //v is not below the bound, the proof would hold another value
+    ValueOutOfRange,
+    //bases needs one point per digit
+    WrongNumberOfBases { required: usize, available: usize },
+    InvalidBase,
+    InvalidBound,
 }
 
 fn get_ith_power_of_2(i: usize) -> Scalar {
@@ -30,13 +75,75 @@ fn get_ith_power_of_2(i: usize) -> Scalar {
     return Scalar::from_bits(bytes);
 }
 
+//The number of bits of a digit, None unless base is 2 or 4, the bases that do not make the
+//proof larger
+fn bits_per_digit(base: u64) -> Option<usize> {
+    return match base {
+        2 => Some(1),
+        4 => Some(2),
+        _ => None,
+    };
+}
+
+//The largest n of a proof of v < 2^n. The order of the group is below 2^253, so from 253 bits
+//on the range no longer restricts v.
+const MAX_RANGE_BITS: usize = 252;
+
+//The number of bits of a digit of a proof of v < 2^n
+fn check_range(n: usize, base: u64) -> Result<usize, RangeProofInputError> {
+    let bits = bits_per_digit(base).ok_or(RangeProofInputError::InvalidBase)?;
+    if n > MAX_RANGE_BITS {
+        return Err(RangeProofInputError::InvalidBound);
+    }
+    return Ok(bits);
+}
+
+//The values each digit of a v below 2^n can take, the top digit has fewer than base of them
+//when b does not divide n
+fn power_of_2_digit_values(n: usize, bits: usize) -> Vec<Vec<Scalar>> {
+    let mut digits = Vec::new();
+    let mut i = 0;
+    while i < n {
+        let digit_bits = if n - i < bits { n - i } else { bits };
+        let weight = get_ith_power_of_2(i);
+        digits.push(
+            (0..1u64 << digit_bits)
+                .map(|a| Scalar::from(a) * weight)
+                .collect(),
+        );
+        i += bits;
+    }
+    return digits;
+}
+
 #[allow(non_snake_case)]
-pub fn verify_range_proof(
+fn digit_tuples(
+    G: EdwardsPoint,
+    S: EdwardsPoint,
+    R: EdwardsPoint,
+    X: EdwardsPoint,
+    values: &[Scalar],
+) -> Vec<VectorTuple> {
+    let K = get_K();
+    return values
+        .iter()
+        .map(|a| VectorTuple {
+            values: vec![
+                CurveVector { x: G, y: S },
+                CurveVector { x: R + a * K, y: X },
+            ],
+        })
+        .collect();
+}
+
+#[allow(non_snake_case)]
+fn verify_digits(
     V: EdwardsPoint,
     bases: &[EdwardsPoint],
-    n: usize,
+    digit_values: &[Vec<Scalar>],
     range_proof: &RangeProof,
 ) -> bool {
+    let n = digit_values.len();
     if bases.len() != n || range_proof.X.len() != n || range_proof.proofs.len() != n {
         return false;
     }
@@ -53,28 +160,8 @@ pub fn verify_range_proof(
     let S = range_proof.S;
 
     for i in 0..n {
-        let proof = &range_proof.proofs[i];
-        let X = range_proof.X[i];
-        let R = bases[i];
-        let W = R + get_ith_power_of_2(i) * get_K();
-
-        let mut one_tuple = Vec::new();
-        one_tuple.push(CurveVector { x: G, y: S });
-        one_tuple.push(CurveVector { x: W, y: X });
-
-        let one_vector_tuple = VectorTuple { values: one_tuple };
-
-        let mut zero_tuple = Vec::new();
-        zero_tuple.push(CurveVector { x: G, y: S });
-        zero_tuple.push(CurveVector { x: R, y: X });
-
-        let zero_vector_tuple = VectorTuple { values: zero_tuple };
-
-        let mut tuples = Vec::new();
-        tuples.push(zero_vector_tuple);
-        tuples.push(one_vector_tuple);
-
-        if !verify_zkplmt(&tuples, proof) {
+        let tuples = digit_tuples(G, S, bases[i], range_proof.X[i], &digit_values[i]);
+        if !verify_zkplmt(&tuples, &range_proof.proofs[i]) {
             return false;
         }
     }
@@ -82,68 +169,375 @@ pub fn verify_range_proof(
     return true;
 }
 
+//digits[i] is the index in digit_values[i] of the value of digit i
 #[allow(non_snake_case)]
-pub fn create_range_proof(v: Scalar, s: Scalar, bases: &[EdwardsPoint]) -> RangeProof {
-    let bytes = v.to_bytes();
-    let mut bits = Vec::new();
-    let mut k = 0;
-    'outer: for i in 0..bytes.len() {
-        let mut value = bytes[i];
-        for j in 0..8 {
-            if k >= bases.len() {
-                break 'outer;
-            }
-            let bit = value & 0x1;
-            if bit > 0 {
-                bits.push(true)
-            } else {
-                bits.push(false);
-            }
-            value = value >> 1;
-            k += 1;
-        }
-    }
-
+fn create_digits(
+    s: Scalar,
+    bases: &[EdwardsPoint],
+    digit_values: &[Vec<Scalar>],
+    digits: &[usize],
+    base: u64,
+) -> RangeProof {
     let G = get_G();
+    let K = get_K();
     let S = s * G;
-    //Now we have the bit values, so we create a proof for each bit
     let mut proofs = Vec::new();
-
     let mut Xs = Vec::new();
-    for i in 0..bits.len() {
+    for i in 0..digits.len() {
         let R = bases[i];
-        let W = R + get_ith_power_of_2(i) * get_K();
-        let X = if bits[i] { s * W } else { s * R };
+        let X = s * (R + digit_values[i][digits[i]] * K);
+        let tuples = digit_tuples(G, S, R, X, &digit_values[i]);
+        proofs.push(create_zkplmt(&tuples, digits[i], s));
+        Xs.push(X);
+    }
+    return RangeProof {
+        proofs: proofs,
+        X: Xs,
+        S: S,
+        base: base,
+    };
+}
 
-        let mut one_tuple = Vec::new();
-        one_tuple.push(CurveVector { x: G, y: S });
-        one_tuple.push(CurveVector { x: W, y: X });
+//Checks V = v*s*K with v < 2^n for a proof of any base, bases has one point per digit
+#[allow(non_snake_case)]
+pub fn verify_range_proof(
+    V: EdwardsPoint,
+    bases: &[EdwardsPoint],
+    n: usize,
+    range_proof: &RangeProof,
+) -> bool {
+    let bits = match check_range(n, range_proof.base) {
+        Ok(bits) => bits,
+        Err(_) => return false,
+    };
+    return verify_digits(V, bases, &power_of_2_digit_values(n, bits), range_proof);
+}
 
-        let one_vector_tuple = VectorTuple { values: one_tuple };
+//A proof of v < 2^n with one bit per base, InvalidBound for more than MAX_RANGE_BITS bases
+pub fn create_range_proof(
+    v: Scalar,
+    s: Scalar,
+    bases: &[EdwardsPoint],
+) -> Result<RangeProof, RangeProofInputError> {
+    return create_range_proof_with_base(v, s, bases, bases.len(), 2);
+}
 
-        let mut zero_tuple = Vec::new();
-        zero_tuple.push(CurveVector { x: G, y: S });
-        zero_tuple.push(CurveVector { x: R, y: X });
+//A proof of v < 2^n with digits of base 2 or 4, InvalidBase for any other base and
+//InvalidBound for n above MAX_RANGE_BITS.
+//bases needs one point per digit, n of them for base 2 and (n + 1) / 2 for base 4.
+pub fn create_range_proof_with_base(
+    v: Scalar,
+    s: Scalar,
+    bases: &[EdwardsPoint],
+    n: usize,
+    base: u64,
+) -> Result<RangeProof, RangeProofInputError> {
+    let bits = check_range(n, base)?;
+    let digit_values = power_of_2_digit_values(n, bits);
+    check_number_of_bases(bases, &digit_values)?;
+    let digits = power_of_2_digits(v, n, bits)?;
+    return Ok(create_digits(s, bases, &digit_values, &digits, base));
+}
 
-        let zero_vector_tuple = VectorTuple { values: zero_tuple };
+fn check_number_of_bases(
+    bases: &[EdwardsPoint],
+    digit_values: &[Vec<Scalar>],
+) -> Result<(), RangeProofInputError> {
+    if bases.len() != digit_values.len() {
+        return Err(RangeProofInputError::WrongNumberOfBases {
+            required: digit_values.len(),
+            available: bases.len(),
+        });
+    }
+    return Ok(());
+}
 
-        let mut tuples = Vec::new();
-        tuples.push(zero_vector_tuple);
-        tuples.push(one_vector_tuple);
+//The digits of v, b bits each, if v < 2^n
+fn power_of_2_digits(v: Scalar, n: usize, bits: usize) -> Result<Vec<usize>, RangeProofInputError> {
+    let bytes = v.to_bytes();
+    let bit = |i: usize| ((bytes[i / 8] >> (i % 8)) & 1) as usize;
+    if (n..256).any(|i| bit(i) == 1) {
+        return Err(RangeProofInputError::ValueOutOfRange);
+    }
+    return Ok((0..(n + bits - 1) / bits)
+        .map(|j| {
+            (0..bits)
+                .filter(|t| j * bits + t < n)
+                .fold(0, |digit, t| digit | (bit(j * bits + t) << t))
+        })
+        .collect());
+}
 
-        let proof = create_zkplmt(&tuples, if bits[i] { 1 } else { 0 }, s);
-        proofs.push(proof);
-        Xs.push(X);
+//The values of the digits of a v below a bound N of base k, 2 or 4. With d digits, the lowest
+//as large as k^(d-1) <= N, the low digits are ordinary digits of base k and cover [0, M) for
+//M = k^(d-1). The top digit takes the values 0, M, .., (q-1)*M for q = N / M and N - M, the
+//intervals [a, a + M) of which cover [0, N) exactly. N - M is left out when q*M = N.
+fn bounded_digit_values(bound: u64, base: u64) -> Vec<Vec<Scalar>> {
+    let (bound, base) = (bound as u128, base as u128);
+    let mut digits = Vec::new();
+    let mut weight = 1u128;
+    while weight * base < bound {
+        digits.push(
+            (0..base)
+                .map(|a| Scalar::from((a * weight) as u64))
+                .collect(),
+        );
+        weight *= base;
     }
-    return RangeProof {
+    let q = bound / weight;
+    let mut top: Vec<Scalar> = (0..q).map(|a| Scalar::from((a * weight) as u64)).collect();
+    if q * weight != bound {
+        top.push(Scalar::from((bound - weight) as u64));
+    }
+    digits.push(top);
+    return digits;
+}
+
+//The indexes in bounded_digit_values of the digits of v
+fn bounded_digits(v: u64, bound: u64, base: u64) -> Vec<usize> {
+    let (v, bound, base) = (v as u128, bound as u128, base as u128);
+    let mut weight = 1u128;
+    let mut num_low_digits = 0;
+    while weight * base < bound {
+        weight *= base;
+        num_low_digits += 1;
+    }
+    let q = bound / weight;
+    let (top, mut rest) = if v < q * weight {
+        ((v / weight) as usize, v % weight)
+    } else {
+        (q as usize, v - (bound - weight))
+    };
+    let mut digits = Vec::new();
+    for _ in 0..num_low_digits {
+        digits.push((rest % base) as usize);
+        rest /= base;
+    }
+    digits.push(top);
+    return digits;
+}
+
+fn check_bound(bound: u64, base: u64) -> Result<(), RangeProofInputError> {
+    if bits_per_digit(base).is_none() {
+        return Err(RangeProofInputError::InvalidBase);
+    }
+    if bound == 0 {
+        return Err(RangeProofInputError::InvalidBound);
+    }
+    return Ok(());
+}
+
+//A proof of v < N for V = v*s*K and any N > 0, with digits of base 2 or 4.
+//bases needs one point per digit, see bounded_digit_values.
+pub fn create_bounded_range_proof(
+    v: u64,
+    s: Scalar,
+    bases: &[EdwardsPoint],
+    bound: u64,
+    base: u64,
+) -> Result<RangeProof, RangeProofInputError> {
+    check_bound(bound, base)?;
+    if v >= bound {
+        return Err(RangeProofInputError::ValueOutOfRange);
+    }
+    let digit_values = bounded_digit_values(bound, base);
+    check_number_of_bases(bases, &digit_values)?;
+    let digits = bounded_digits(v, bound, base);
+    return Ok(create_digits(s, bases, &digit_values, &digits, base));
+}
+
+//The number of points bases needs for a proof of v < bound
+pub fn bounded_range_proof_num_bases(bound: u64, base: u64) -> Result<usize, RangeProofInputError> {
+    check_bound(bound, base)?;
+    return Ok(bounded_digit_values(bound, base).len());
+}
+
+#[allow(non_snake_case)]
+pub fn verify_bounded_range_proof(
+    V: EdwardsPoint,
+    bases: &[EdwardsPoint],
+    bound: u64,
+    range_proof: &RangeProof,
+) -> bool {
+    if check_bound(bound, range_proof.base).is_err() {
+        return false;
+    }
+    return verify_digits(
+        V,
+        bases,
+        &bounded_digit_values(bound, range_proof.base),
+        range_proof,
+    );
+}
+
+//The same digit rings over a Pedersen commitment V = v*GInit + gamma*HInit as used by
+//BulletRangeProof. Digit i is committed as C_i = a_i*GInit + r_i*HInit with the r_i adding up
+//to gamma, and its ring shows C_i - a*GInit is a multiple of HInit for one allowed a.
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PedersenRangeProof {
+    proofs: Vec<Proof>,
+    C: Vec<EdwardsPoint>,
+    base: u64,
+}
+
+#[allow(non_snake_case)]
+fn pedersen_digit_tuples(bases: &Bases, C: EdwardsPoint, values: &[Scalar]) -> Vec<VectorTuple> {
+    return values
+        .iter()
+        .map(|a| VectorTuple {
+            values: vec![CurveVector {
+                x: bases.HInit,
+                y: C - a * bases.GInit,
+            }],
+        })
+        .collect();
+}
+
+//A proof of v < 2^n for V = v*GInit + gamma*HInit with digits of base 2 or 4.
+#[allow(non_snake_case)]
+pub fn create_pedersen_range_proof(
+    v: Scalar,
+    gamma: Scalar,
+    bases: &Bases,
+    n: usize,
+    base: u64,
+) -> Result<PedersenRangeProof, RangeProofInputError> {
+    let bits = check_range(n, base)?;
+    let digit_values = power_of_2_digit_values(n, bits);
+    let digits = power_of_2_digits(v, n, bits)?;
+
+    let mut csprng: OsRng = OsRng::default();
+    let mut r: Vec<Scalar> = (1..digits.len())
+        .map(|_| Scalar::random(&mut csprng))
+        .collect();
+    r.push(gamma - r.iter().fold(Scalar::zero(), |sum, r| sum + r));
+
+    let mut proofs = Vec::new();
+    let mut Cs = Vec::new();
+    for i in 0..digits.len() {
+        let C = digit_values[i][digits[i]] * bases.GInit + r[i] * bases.HInit;
+        let tuples = pedersen_digit_tuples(bases, C, &digit_values[i]);
+        proofs.push(create_zkplmt(&tuples, digits[i], r[i]));
+        Cs.push(C);
+    }
+    return Ok(PedersenRangeProof {
         proofs: proofs,
-        X: Xs,
-        S: S,
+        C: Cs,
+        base: base,
+    });
+}
+
+#[allow(non_snake_case)]
+pub fn verify_pedersen_range_proof(
+    V: EdwardsPoint,
+    bases: &Bases,
+    n: usize,
+    range_proof: &PedersenRangeProof,
+) -> bool {
+    let bits = match check_range(n, range_proof.base) {
+        Ok(bits) => bits,
+        Err(_) => return false,
+    };
+    let digit_values = power_of_2_digit_values(n, bits);
+    if range_proof.C.len() != digit_values.len() || range_proof.proofs.len() != digit_values.len() {
+        return false;
+    }
+    if V != range_proof
+        .C
+        .iter()
+        .fold(EdwardsPoint::default(), |sum, C| sum + C)
+    {
+        return false;
+    }
+    for i in 0..digit_values.len() {
+        let tuples = pedersen_digit_tuples(bases, range_proof.C[i], &digit_values[i]);
+        if !verify_zkplmt(&tuples, &range_proof.proofs[i]) {
+            return false;
+        }
+    }
+    return true;
+}
+
+//Shows that W = v*s*K with S = s*G, the commitment of create_range_proof and of
+//ValueCommitmentPublic, and V = v*GInit + gamma*HInit hold the same v, so that the value can
+//move to a BulletRangeProof. With u = s^-1 the relations u*S = G, u*W - v*K = 0 and
+//v*GInit + gamma*HInit = V are linear in (u, v, gamma) and proved by a single Schnorr proof.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CommitmentEqualityProof {
+    e: Scalar,
+    z_u: Scalar,
+    z_v: Scalar,
+    z_gamma: Scalar,
+}
+
+//Hashes a label, GInit and HInit before the points so that the proof holds for these bases only
+#[allow(non_snake_case)]
+fn commitment_equality_challenge(bases: &Bases, points: &[EdwardsPoint]) -> Scalar {
+    let label = b"commitment equality";
+    let mut hash_input = vec![0u8; label.len() + 32 * (points.len() + 2)];
+    copy(&mut hash_input, label);
+    let all_points = [bases.GInit, bases.HInit];
+    for (i, point) in all_points.iter().chain(points.iter()).enumerate() {
+        copy(
+            &mut hash_input[label.len() + 32 * i..],
+            &point.compress().to_bytes(),
+        );
+    }
+    return Scalar::hash_from_bytes::<Sha512>(&hash_input);
+}
+
+#[allow(non_snake_case)]
+pub fn create_commitment_equality_proof(
+    v: Scalar,
+    s: Scalar,
+    gamma: Scalar,
+    bases: &Bases,
+) -> CommitmentEqualityProof {
+    let G = get_G();
+    let K = get_K();
+    let S = s * G;
+    let W = v * s * K;
+    let V = v * bases.GInit + gamma * bases.HInit;
+    let u = s.invert();
+
+    let mut csprng: OsRng = OsRng::default();
+    let k_u = Scalar::random(&mut csprng);
+    let k_v = Scalar::random(&mut csprng);
+    let k_gamma = Scalar::random(&mut csprng);
+    let T1 = k_u * S;
+    let T2 = k_u * W - k_v * K;
+    let T3 = k_v * bases.GInit + k_gamma * bases.HInit;
+    let e = commitment_equality_challenge(bases, &[S, W, V, T1, T2, T3]);
+    return CommitmentEqualityProof {
+        e: e,
+        z_u: k_u + e * u,
+        z_v: k_v + e * v,
+        z_gamma: k_gamma + e * gamma,
     };
 }
 
+#[allow(non_snake_case)]
+pub fn verify_commitment_equality_proof(
+    S: EdwardsPoint,
+    W: EdwardsPoint,
+    V: EdwardsPoint,
+    bases: &Bases,
+    proof: &CommitmentEqualityProof,
+) -> bool {
+    let e = proof.e;
+    let T1 = EdwardsPoint::multiscalar_mul(&[proof.z_u, -e], &[S, get_G()]);
+    let T2 = EdwardsPoint::multiscalar_mul(&[proof.z_u, -proof.z_v], &[W, get_K()]);
+    let T3 = EdwardsPoint::multiscalar_mul(
+        &[proof.z_v, proof.z_gamma, -e],
+        &[bases.GInit, bases.HInit, V],
+    );
+    return e == commitment_equality_challenge(bases, &[S, W, V, T1, T2, T3]);
+}
+
 #[cfg(test)]
 mod tests {
+    use super::super::bulletproofs::*;
     use super::super::value_bound_signature::*;
     use super::*;
 
@@ -238,7 +632,7 @@ mod tests {
             bases.push(R);
         }
         bases.push((-sum) * G);
-        let range_proof = create_range_proof(v, s, &bases);
+        let range_proof = create_range_proof(v, s, &bases).unwrap();
         assert!(verify_range_proof(V, &bases, n, &range_proof));
 
         //////////////////////////////////////////////////
@@ -261,7 +655,274 @@ mod tests {
         }
         bases.push((-sum) * G);
 
-        let range_proof = create_range_proof(v, s, &bases);
+        let range_proof = create_range_proof(v, s, &bases).unwrap();
         assert!(verify_range_proof(V, &bases, n, &range_proof));
     }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_range_proof_with_base() {
+        let mut csprng: OsRng = OsRng::default();
+        let s = Scalar::random(&mut csprng);
+        let G = get_G();
+        let K = get_K();
+        let random_bases = |n: usize| {
+            let mut bases: Vec<EdwardsPoint> = (0..n - 1)
+                .map(|_| Scalar::random(&mut OsRng::default()) * G)
+                .collect();
+            let sum = bases.iter().fold(EdwardsPoint::default(), |X, Y| X + Y);
+            bases.push(-sum);
+            bases
+        };
+
+        let v = Scalar::from(0x5f3a1u64);
+        let V = v * s * K;
+        //19 bits, 10 digits of base 4 with a top digit of 1 bit
+        let bases = random_bases(10);
+        let range_proof = create_range_proof_with_base(v, s, &bases, 19, 4).unwrap();
+        assert_eq!(range_proof.X.len(), 10);
+        assert!(verify_range_proof(V, &bases, 19, &range_proof));
+        assert!(!verify_range_proof(V, &bases, 20, &range_proof));
+        assert!(!verify_range_proof(V + K, &bases, 19, &range_proof));
+
+        //a base 2 proof is the bitwise proof
+        let bases = random_bases(19);
+        let range_proof = create_range_proof_with_base(v, s, &bases, 19, 2).unwrap();
+        assert!(verify_range_proof(V, &bases, 19, &range_proof));
+
+        //the top digit only holds one bit, so 2^19 does not fit
+        let bases = random_bases(10);
+        assert_eq!(
+            create_range_proof_with_base(Scalar::from(1u64 << 19), s, &bases, 19, 4).err(),
+            Some(RangeProofInputError::ValueOutOfRange)
+        );
+        let bases = random_bases(7);
+        assert_eq!(
+            create_range_proof_with_base(v, s, &bases, 19, 4).err(),
+            Some(RangeProofInputError::WrongNumberOfBases {
+                required: 10,
+                available: 7
+            })
+        );
+        for base in [3u64, 8, 16].iter() {
+            assert_eq!(
+                create_range_proof_with_base(v, s, &bases, 19, *base).err(),
+                Some(RangeProofInputError::InvalidBase)
+            );
+        }
+
+        //from 253 bits on every scalar is in range
+        let bases = random_bases(253);
+        assert_eq!(
+            create_range_proof(v, s, &bases).err(),
+            Some(RangeProofInputError::InvalidBound)
+        );
+        assert_eq!(
+            create_range_proof_with_base(v, s, &bases[..150], 300, 4).err(),
+            Some(RangeProofInputError::InvalidBound)
+        );
+        assert!(!verify_range_proof(V, &bases, 253, &range_proof));
+        assert_eq!(RangeProof::serialized_size(253, 2), None);
+        let bases = random_bases(252);
+        assert!(create_range_proof(v, s, &bases).is_ok());
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_range_proof_serialized_size() {
+        let s = Scalar::random(&mut OsRng::default());
+        let G = get_G();
+        for (n, base) in [(64usize, 2u64), (64, 4), (19, 4)].iter() {
+            let digits = if *base == 2 { *n } else { (n + 1) / 2 };
+            let mut bases: Vec<EdwardsPoint> = (0..digits - 1)
+                .map(|_| Scalar::random(&mut OsRng::default()) * G)
+                .collect();
+            bases.push(-bases.iter().fold(EdwardsPoint::default(), |X, Y| X + Y));
+            let range_proof =
+                create_range_proof_with_base(Scalar::from(12345u64), s, &bases, *n, *base).unwrap();
+            assert_eq!(
+                Some(bincode::serialize(&range_proof).unwrap().len()),
+                RangeProof::serialized_size(*n, *base)
+            );
+        }
+        //288 points and scalars instead of 320 for 64 bits, besides S and the lengths
+        let binary = RangeProof::serialized_size(64, 2).unwrap();
+        let quaternary = RangeProof::serialized_size(64, 4).unwrap();
+        assert!(quaternary < binary);
+        assert_eq!(binary - 32 * 320, 8 + 64 * 16 + 8 + 32 + 8);
+        assert_eq!(quaternary - 32 * 288, 8 + 32 * 16 + 8 + 32 + 8);
+        assert_eq!(RangeProof::serialized_size(64, 8), None);
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_bounded_range_proof() {
+        let mut csprng: OsRng = OsRng::default();
+        let s = Scalar::random(&mut csprng);
+        let G = get_G();
+        let K = get_K();
+        let random_bases = |n: usize| {
+            let mut bases: Vec<EdwardsPoint> = (0..n - 1)
+                .map(|_| Scalar::random(&mut OsRng::default()) * G)
+                .collect();
+            let sum = bases.iter().fold(EdwardsPoint::default(), |X, Y| X + Y);
+            bases.push(-sum);
+            bases
+        };
+
+        for (bound, base) in [(1000u64, 2u64), (1000, 4), (1024, 4), (7, 2), (7, 4)].iter() {
+            let bases = random_bases(bounded_range_proof_num_bases(*bound, *base).unwrap());
+            for v in [0, 1, bound / 2, bound - 2, bound - 1].iter() {
+                let range_proof = create_bounded_range_proof(*v, s, &bases, *bound, *base).unwrap();
+                let V = Scalar::from(*v) * s * K;
+                assert!(verify_bounded_range_proof(V, &bases, *bound, &range_proof));
+                assert!(!verify_bounded_range_proof(
+                    V + K,
+                    &bases,
+                    *bound,
+                    &range_proof
+                ));
+            }
+            assert_eq!(
+                create_bounded_range_proof(*bound, s, &bases, *bound, *base).err(),
+                Some(RangeProofInputError::ValueOutOfRange)
+            );
+        }
+
+        //every value the rings allow is below the bound
+        for (bound, base) in [
+            (1000u64, 2u64),
+            (1000, 4),
+            (37, 4),
+            (u64::MAX, 2),
+            (u64::MAX, 4),
+        ]
+        .iter()
+        {
+            let digit_values = bounded_digit_values(*bound, *base);
+            let max = digit_values.iter().fold(Scalar::zero(), |sum, values| {
+                sum + values.iter().fold(Scalar::zero(), |max, a| {
+                    if a.as_bytes().iter().rev().ge(max.as_bytes().iter().rev()) {
+                        *a
+                    } else {
+                        max
+                    }
+                })
+            });
+            assert_eq!(max, Scalar::from(bound - 1));
+        }
+
+        let bases = random_bases(10);
+        for base in [0u64, 1, 3, 10, 256].iter() {
+            assert_eq!(
+                bounded_range_proof_num_bases(1000, *base),
+                Err(RangeProofInputError::InvalidBase)
+            );
+            assert_eq!(
+                create_bounded_range_proof(5, s, &bases, 1000, *base).err(),
+                Some(RangeProofInputError::InvalidBase)
+            );
+        }
+        assert_eq!(
+            bounded_range_proof_num_bases(0, 4),
+            Err(RangeProofInputError::InvalidBound)
+        );
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_pedersen_range_proof() {
+        let bases = Bases::new(get_L(), get_G(), 1);
+        let mut csprng: OsRng = OsRng::default();
+        let gamma = Scalar::random(&mut csprng);
+        let v = Scalar::from(0xc0ffeeu64);
+        let V = v * bases.GInit + gamma * bases.HInit;
+        assert_eq!(
+            create_pedersen_range_proof(v, gamma, &bases, 24, 16).err(),
+            Some(RangeProofInputError::InvalidBase)
+        );
+        assert_eq!(
+            create_pedersen_range_proof(v, gamma, &bases, 300, 4).err(),
+            Some(RangeProofInputError::InvalidBound)
+        );
+        for base in [2u64, 4].iter() {
+            let range_proof = create_pedersen_range_proof(v, gamma, &bases, 24, *base).unwrap();
+            assert!(verify_pedersen_range_proof(V, &bases, 24, &range_proof));
+            assert!(!verify_pedersen_range_proof(V, &bases, 23, &range_proof));
+            assert!(!verify_pedersen_range_proof(
+                V + bases.GInit,
+                &bases,
+                24,
+                &range_proof
+            ));
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_commitment_equality_proof() {
+        let bases = Bases::new(get_L(), get_G(), 1);
+        let mut csprng: OsRng = OsRng::default();
+        let s = Scalar::random(&mut csprng);
+        let gamma = Scalar::random(&mut csprng);
+        let v = 5000u64;
+        let S = s * get_G();
+        let W = Scalar::from(v) * s * get_K();
+        let V = Scalar::from(v) * bases.GInit + gamma * bases.HInit;
+
+        let proof = create_commitment_equality_proof(Scalar::from(v), s, gamma, &bases);
+        assert!(verify_commitment_equality_proof(S, W, V, &bases, &proof));
+        assert!(!verify_commitment_equality_proof(
+            S,
+            W,
+            V + bases.GInit,
+            &bases,
+            &proof
+        ));
+        assert!(!verify_commitment_equality_proof(
+            S,
+            W + get_K(),
+            V,
+            &bases,
+            &proof
+        ));
+
+        let other_bases = Bases::new(get_G(), get_L(), 1);
+        assert!(!verify_commitment_equality_proof(
+            S,
+            W,
+            V,
+            &other_bases,
+            &proof
+        ));
+
+        //the value then moves to a bulletproof over V
+        let bullet_proof = bullet_range_proof(&[gamma], &[v], &bases).unwrap();
+        assert_eq!(bullet_proof.V[0], V);
+        assert!(bullet_range_verify(&bullet_proof, &bases));
+    }
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_legacy_interop_serialization() {
+        let bases = Bases::new(get_L(), get_G(), 1);
+        let mut csprng: OsRng = OsRng::default();
+        let s = Scalar::random(&mut csprng);
+        let gamma = Scalar::random(&mut csprng);
+        let v = Scalar::from(77u64);
+        let S = s * get_G();
+        let W = v * s * get_K();
+        let V = v * bases.GInit + gamma * bases.HInit;
+
+        let range_proof = create_pedersen_range_proof(v, gamma, &bases, 8, 4).unwrap();
+        let encoded = bincode::serialize(&range_proof).unwrap();
+        let decoded: PedersenRangeProof = bincode::deserialize(&encoded).unwrap();
+        assert_eq!(decoded, range_proof);
+        assert!(verify_pedersen_range_proof(V, &bases, 8, &decoded));
+
+        let proof = create_commitment_equality_proof(v, s, gamma, &bases);
+        let encoded = bincode::serialize(&proof).unwrap();
+        let decoded: CommitmentEqualityProof = bincode::deserialize(&encoded).unwrap();
+        assert_eq!(decoded, proof);
+        assert!(verify_commitment_equality_proof(S, W, V, &bases, &decoded));
+    }
 }
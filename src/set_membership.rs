@@ -0,0 +1,246 @@
+//Proves that the value committed in V = v*GInit + gamma*HInit is one of a public set
+//{s_1..s_k}, e.g. the allowed denominations or asset ids. V - s_i*GInit = gamma*HInit for the
+//s_i equal to v, so a zkplmt over the tuples (HInit, V - s_i*GInit) shows the prover knows
+//gamma for one of them without telling which.
+//
+//The proof also carries the commitment L_i = c_i*HInit + d_i*(V - s_i*GInit) of every tuple,
+//2k scalars and k points in all. The challenge is then hashed from points the verifier
+//already has, and what remains are equations linear in HInit, GInit, V and the L_i, which the
+//proofs over a set fold into a single multiscalar multiplication with random weights.
+//
+//The points s_i*GInit are computed once in a CommittedSet and shared by all the proofs over
+//the same set.
+
+use super::bulletproofs::*;
+use super::core::*;
+use alloc::vec::*;
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::IsIdentity;
+use curve25519_dalek::traits::MultiscalarMul;
+use curve25519_dalek::traits::VartimeMultiscalarMul;
+use rand_core::OsRng;
+use serde::*;
+use sha2::Sha512;
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommittedSet {
+    GInit: EdwardsPoint,
+    HInit: EdwardsPoint,
+    elements: Vec<u64>,
+    //s_i*GInit
+    points: Vec<EdwardsPoint>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SetMembershipProof {
+    V: EdwardsPoint,
+    //the L of each tuple the challenge is hashed from
+    commitments: Vec<EdwardsPoint>,
+    proof: Proof,
+}
+
+impl SetMembershipProof {
+    //The commitment v*GInit + gamma*HInit
+    #[allow(non_snake_case)]
+    pub fn V(&self) -> EdwardsPoint {
+        return self.V;
+    }
+}
+
+impl CommittedSet {
+    pub fn new(bases: &Bases, elements: &[u64]) -> CommittedSet {
+        let points = elements
+            .iter()
+            .map(|s| Scalar::from(*s) * bases.GInit)
+            .collect();
+        CommittedSet {
+            GInit: bases.GInit,
+            HInit: bases.HInit,
+            elements: elements.to_vec(),
+            points,
+        }
+    }
+
+    pub fn elements(&self) -> &[u64] {
+        return &self.elements;
+    }
+
+    #[allow(non_snake_case)]
+    fn tuples(&self, V: &EdwardsPoint) -> Vec<VectorTuple> {
+        return self
+            .points
+            .iter()
+            .map(|point| VectorTuple {
+                values: vec![CurveVector {
+                    x: self.HInit,
+                    y: V - point,
+                }],
+            })
+            .collect();
+    }
+}
+
+//None if v is not in the set
+#[allow(non_snake_case)]
+pub fn prove_set_membership(
+    set: &CommittedSet,
+    v: u64,
+    gamma: Scalar,
+) -> Option<SetMembershipProof> {
+    let hidden_index = set.elements.iter().position(|s| *s == v)?;
+    let V = Scalar::from(v) * set.GInit + gamma * set.HInit;
+    let tuples = set.tuples(&V);
+    let proof = create_zkplmt(&tuples, hidden_index, gamma);
+    let commitments = tuples
+        .iter()
+        .enumerate()
+        .map(|(j, tuple)| {
+            EdwardsPoint::multiscalar_mul(
+                &[proof.c()[j], proof.d()[j]],
+                &[tuple.values[0].x, tuple.values[0].y],
+            )
+        })
+        .collect();
+    return Some(SetMembershipProof {
+        V,
+        commitments,
+        proof,
+    });
+}
+
+//Checks the challenge of the proof is the hash of its commitments, false for a proof that does
+//not have one commitment and one (c, d) per element of the set
+fn challenge_matches(set: &CommittedSet, proof: &SetMembershipProof) -> bool {
+    let k = set.points.len();
+    if k == 0
+        || proof.commitments.len() != k
+        || proof.proof.c().len() != k
+        || proof.proof.d().len() != k
+    {
+        return false;
+    }
+    let hash_input = zkplmt_hash_input(&set.tuples(&proof.V), |j, _| proof.commitments[j]);
+    return Scalar::hash_from_bytes::<Sha512>(&hash_input) == zkplmt_challenge(&proof.proof);
+}
+
+pub fn verify_set_membership(set: &CommittedSet, proof: &SetMembershipProof) -> bool {
+    if !challenge_matches(set, proof) {
+        return false;
+    }
+    let (c, d) = (proof.proof.c(), proof.proof.d());
+    return (0..set.points.len()).all(|j| {
+        proof.commitments[j]
+            == EdwardsPoint::multiscalar_mul(&[c[j], d[j]], &[set.HInit, proof.V - set.points[j]])
+    });
+}
+
+//Verifies proofs over the same set with a single multiscalar multiplication. The equation
+//c_j*HInit + d_j*V - d_j*s_j*GInit - L_j = 0 of every tuple of every proof is multiplied by a
+//fresh random weight before they are summed, so a batch holding an invalid proof passes only
+//with negligible probability. If the batch fails the proofs are verified one by one and the
+//index of the first invalid one is returned.
+pub fn verify_set_membership_all(
+    set: &CommittedSet,
+    proofs: &[SetMembershipProof],
+) -> Result<(), usize> {
+    let mut csprng: OsRng = OsRng::default();
+    let mut g = Scalar::zero();
+    let mut h = Scalar::zero();
+    let mut scalars = Vec::new();
+    let mut points = Vec::new();
+    let mut well_formed = true;
+    for proof in proofs {
+        if !challenge_matches(set, proof) {
+            well_formed = false;
+            break;
+        }
+        let (c, d) = (proof.proof.c(), proof.proof.d());
+        let mut v = Scalar::zero();
+        for j in 0..set.points.len() {
+            let w = Scalar::random(&mut csprng);
+            h += w * c[j];
+            g -= w * d[j] * Scalar::from(set.elements[j]);
+            v += w * d[j];
+            scalars.push(-w);
+            points.push(proof.commitments[j]);
+        }
+        scalars.push(v);
+        points.push(proof.V);
+    }
+    scalars.push(g);
+    points.push(set.GInit);
+    scalars.push(h);
+    points.push(set.HInit);
+    if well_formed && EdwardsPoint::vartime_multiscalar_mul(&scalars, &points).is_identity() {
+        return Ok(());
+    }
+
+    for i in 0..proofs.len() {
+        if !verify_set_membership(set, &proofs[i]) {
+            return Err(i);
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::OsRng;
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_set_membership() {
+        let bases = Bases::new(get_L(), get_K(), 1);
+        let set = CommittedSet::new(&bases, &[0, 5, 10, 50, 100]);
+        let mut csprng: OsRng = OsRng::default();
+
+        let mut proofs = Vec::new();
+        for v in set.elements().iter() {
+            let gamma = Scalar::random(&mut csprng);
+            let proof = prove_set_membership(&set, *v, gamma).unwrap();
+            assert_eq!(
+                proof.V(),
+                Scalar::from(*v) * bases.GInit + gamma * bases.HInit
+            );
+            assert!(verify_set_membership(&set, &proof));
+            proofs.push(proof);
+        }
+        assert!(prove_set_membership(&set, 20, Scalar::one()).is_none());
+
+        assert_eq!(verify_set_membership_all(&set, &proofs), Ok(()));
+        assert_eq!(verify_set_membership_all(&set, &[]), Ok(()));
+
+        //the challenge does not depend on c, so only the batched equation catches a wrong one
+        let mut wrong_c = proofs.clone();
+        let mut bytes = wrong_c[3].proof.to_bytes();
+        let c = read_scalar(&bytes[32..]).unwrap();
+        bytes[32..64].copy_from_slice((c + Scalar::one()).as_bytes());
+        wrong_c[3].proof = Proof::from_bytes(&bytes).unwrap();
+        assert!(challenge_matches(&set, &wrong_c[3]));
+        assert!(!verify_set_membership(&set, &wrong_c[3]));
+        assert_eq!(verify_set_membership_all(&set, &wrong_c), Err(3));
+
+        let mut wrong_commitment = proofs.clone();
+        wrong_commitment[4].commitments[1] += bases.HInit;
+        assert!(!verify_set_membership(&set, &wrong_commitment[4]));
+        assert_eq!(verify_set_membership_all(&set, &wrong_commitment), Err(4));
+
+        //a proof for 10 does not hold for 20
+        proofs[2].V += Scalar::from(10u64) * bases.GInit;
+        assert!(!verify_set_membership(&set, &proofs[2]));
+        assert_eq!(verify_set_membership_all(&set, &proofs), Err(2));
+        proofs[2].commitments.pop();
+        assert_eq!(verify_set_membership_all(&set, &proofs), Err(2));
+
+        let other_set = CommittedSet::new(&bases, &[0, 5, 11, 50, 100]);
+        assert!(!verify_set_membership(&other_set, &proofs[1]));
+        assert!(!verify_set_membership(
+            &CommittedSet::new(&bases, &[]),
+            &proofs[1]
+        ));
+    }
+}
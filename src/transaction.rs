@@ -494,8 +494,14 @@ pub fn create_transaction(
     p: Scalar,
     AP_public_key: EdwardsPoint,
     kyc_sources: Vec<SpendingLimitProof>,
-    bases: Bases,
-) -> Transaction {
+    bases: &Bases,
+) -> Result<Transaction, RangeProverError> {
+    if vo.is_empty() {
+        return Err(RangeProverError::NoValues);
+    }
+    bases
+        .check_capacity(vo.len())
+        .map_err(RangeProverError::Bases)?;
     let G = get_G();
     let mut csprng: OsRng = OsRng::default();
     let z = Scalar::random(&mut csprng);
@@ -637,7 +643,7 @@ pub fn create_transaction(
     }
     let mut kyc_sources = kyc_sources.clone();
     let k = shuffle(&mut kyc_sources);
-    let range_proof = bullet_range_proof(&ro, &vo, &bases);
+    let range_proof = bullet_range_proof(&ro, &vo, bases)?;
     let spendingLimitProof = generate_KYC_proof(
         kyc_sources,
         output_pub_keys[0].0,
@@ -649,7 +655,7 @@ pub fn create_transaction(
         p,
     );
 
-    Transaction {
+    Ok(Transaction {
         input_condidates: inputs_shuffle,
         outputs: outputs,
         key_images: key_images,
@@ -662,11 +668,32 @@ pub fn create_transaction(
         AP_Declaration_Of_Value: AP_Declaration_Of_Value,
         range_proof: range_proof,
         spendingLimitProof: spendingLimitProof,
-    }
+    })
+}
+
+pub fn verify_transaction(transaction: &Transaction, bases: &Bases) -> bool {
+    return verify_transaction_detailed(transaction, bases).is_ok();
+}
+
+//The part of a transaction that failed verification
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TransactionError {
+    AlphaProof,
+    APDeclarationOfPublicKey,
+    APDeclarationOfValue { output: usize },
+    //the range proof is not over the output commitments
+    RangeProofCommitments,
+    RangeProof(RangeProofError),
+    SpendingLimitProof,
+    //the zkplmt over the input candidates
+    RingProof,
 }
 
 #[allow(non_snake_case)]
-pub fn verify_transaction(transaction: &Transaction, bases: Bases) -> bool {
+pub fn verify_transaction_detailed(
+    transaction: &Transaction,
+    bases: &Bases,
+) -> Result<(), TransactionError> {
     let G = get_G();
     let alpha_tuple = VectorTuple {
         values: vec![CurveVector {
@@ -675,7 +702,7 @@ pub fn verify_transaction(transaction: &Transaction, bases: Bases) -> bool {
         }],
     };
     if !verify_zkplmt(&[alpha_tuple], &transaction.alpha) {
-        return false;
+        return Err(TransactionError::AlphaProof);
     }
 
     let H: Vec<Vec<EdwardsPoint>> = transaction
@@ -753,21 +780,25 @@ pub fn verify_transaction(transaction: &Transaction, bases: Bases) -> bool {
         transaction.S_vector.y,
         transaction.AP_public_key,
     ) {
-        return false;
+        return Err(TransactionError::APDeclarationOfPublicKey);
     }
 
     let L = get_L();
 
-    for (output, dec) in transaction
+    for (i, (output, dec)) in transaction
         .outputs
         .iter()
         .zip(transaction.AP_Declaration_Of_Value.iter())
+        .enumerate()
     {
         let V = output.commitment;
         if !verify_AP_declaration_of_value(V, dec.clone(), &G, &L, &transaction.AP_public_key) {
-            return false;
+            return Err(TransactionError::APDeclarationOfValue { output: i });
         }
     }
+    if transaction.range_proof.V.len() != transaction.outputs.len() {
+        return Err(TransactionError::RangeProofCommitments);
+    }
     let mut outComms = vec![];
     for i in 0..transaction.outputs.len() {
         let com = transaction.outputs[i].commitment;
@@ -775,13 +806,12 @@ pub fn verify_transaction(transaction: &Transaction, bases: Bases) -> bool {
         let rcom = transaction.range_proof.V[i];
         //assert_eq!(com, rcom);
         if !com.eq(&rcom) {
-            return false;
+            return Err(TransactionError::RangeProofCommitments);
         }
     }
 
-    if !bullet_range_verify(&transaction.range_proof, bases) {
-        return false;
-    }
+    bullet_range_verify_detailed(&transaction.range_proof, bases)
+        .map_err(TransactionError::RangeProof)?;
     if !verify_KYC_proof(
         &transaction.spendingLimitProof,
         transaction.outputs[0].public_key.0,
@@ -790,10 +820,13 @@ pub fn verify_transaction(transaction: &Transaction, bases: Bases) -> bool {
         &transaction.S_vector.x,
         &transaction.S_vector.y,
     ) {
-        return false;
+        return Err(TransactionError::SpendingLimitProof);
     }
 
-    return verify_zkplmt(&tuples, &transaction.pi);
+    if !verify_zkplmt(&tuples, &transaction.pi) {
+        return Err(TransactionError::RingProof);
+    }
+    return Ok(());
 }
 
 #[cfg(test)]
@@ -898,11 +931,14 @@ mod tests {
             p,
             AP_public_key,
             kyc_sources,
-            bases.clone(),
-        );
-        assert!(verify_transaction(&transaction, bases));
+            &bases,
+        )
+        .unwrap();
+        assert!(verify_transaction(&transaction, &bases));
     }
 
+    #[allow(non_snake_case)]
+    #[test]
     fn test_serialization() {
         let ginit = get_G();
         let G = get_G();
@@ -994,10 +1030,33 @@ mod tests {
             p,
             AP_public_key,
             kyc_sources,
-            bases.clone(),
-        );
+            &bases,
+        )
+        .unwrap();
         let ser = bincode::serialize(&transaction).unwrap();
         let dec:Transaction = bincode::deserialize(&ser).unwrap();
-        assert!(verify_transaction(&dec, bases));
+        assert!(verify_transaction(&dec, &bases));
+        assert_eq!(verify_transaction_detailed(&dec, &bases), Ok(()));
+
+        let mut tampered = dec.clone();
+        tampered.range_proof.t_cap += Scalar::one();
+        assert_eq!(
+            verify_transaction_detailed(&tampered, &bases),
+            Err(TransactionError::RangeProof(
+                RangeProofError::PolynomialCheck
+            ))
+        );
+        let mut tampered = dec.clone();
+        tampered.range_proof.V.swap(0, 1);
+        assert_eq!(
+            verify_transaction_detailed(&tampered, &bases),
+            Err(TransactionError::RangeProofCommitments)
+        );
+        let mut tampered = dec.clone();
+        tampered.key_images[0] = get_random_curve_point();
+        assert_eq!(
+            verify_transaction_detailed(&tampered, &bases),
+            Err(TransactionError::RingProof)
+        );
     }
 }
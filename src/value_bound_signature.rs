@@ -1,19 +1,24 @@
 use super::core::*;
+use super::range_proof::*;
+use alloc::collections::BTreeSet;
 use alloc::vec::*;
 use curve25519_dalek::edwards::CompressedEdwardsY;
 use curve25519_dalek::edwards::EdwardsPoint;
 use curve25519_dalek::scalar::Scalar;
 use curve25519_dalek::traits::IsIdentity;
+use curve25519_dalek::traits::MultiscalarMul;
+use sha2::Sha512;
 
 use rand_core::RngCore;
 use rand_core::OsRng;
+use serde::*;
 
 #[derive(Clone, Copy)]
 struct Source {
     one_time_key: CurveVector,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[allow(non_snake_case)]
 pub struct ValueCommitmentPublic {
     S: EdwardsPoint,
@@ -22,7 +27,7 @@ pub struct ValueCommitmentPublic {
     B: EdwardsPoint,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[allow(non_snake_case)]
 pub struct ValueCommitment {
     value_commitment_public: ValueCommitmentPublic,
@@ -30,7 +35,7 @@ pub struct ValueCommitment {
     s: Scalar,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[allow(non_snake_case)]
 pub struct BlindingSignature {
     value_commitment_public: ValueCommitmentPublic,
@@ -38,6 +43,153 @@ pub struct BlindingSignature {
     I_: EdwardsPoint,
 }
 
+//S = s*G, V = v*s*K and the one time key (A, B = p*A) of the owner
+#[allow(non_snake_case)]
+impl ValueCommitmentPublic {
+    pub fn new(S: EdwardsPoint, V: EdwardsPoint, A: EdwardsPoint, B: EdwardsPoint) -> Self {
+        ValueCommitmentPublic {
+            S: S,
+            V: V,
+            A: A,
+            B: B,
+        }
+    }
+
+    pub fn S(&self) -> EdwardsPoint {
+        return self.S;
+    }
+
+    pub fn V(&self) -> EdwardsPoint {
+        return self.V;
+    }
+
+    pub fn A(&self) -> EdwardsPoint {
+        return self.A;
+    }
+
+    pub fn B(&self) -> EdwardsPoint {
+        return self.B;
+    }
+
+    //S, V, A and B
+    pub fn to_bytes(&self) -> [u8; 128] {
+        let mut bytes = [0u8; 128];
+        for (i, point) in [self.S, self.V, self.A, self.B].iter().enumerate() {
+            copy(&mut bytes[32 * i..], &point.compress().to_bytes());
+        }
+        return bytes;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() != 128 {
+            return Err(DecodeError::WrongLength);
+        }
+        return Ok(ValueCommitmentPublic {
+            S: read_point(bytes)?,
+            V: read_point(&bytes[32..])?,
+            A: read_point(&bytes[64..])?,
+            B: read_point(&bytes[96..])?,
+        });
+    }
+}
+
+#[allow(non_snake_case)]
+impl ValueCommitment {
+    //The commitment to v with the blinding key s for the one time key (A, B)
+    pub fn new(v: Scalar, s: Scalar, A: EdwardsPoint, B: EdwardsPoint) -> Self {
+        ValueCommitment {
+            value_commitment_public: ValueCommitmentPublic {
+                S: s * get_G(),
+                V: v * s * get_K(),
+                A: A,
+                B: B,
+            },
+            v: v,
+            s: s,
+        }
+    }
+
+    pub fn public(&self) -> &ValueCommitmentPublic {
+        return &self.value_commitment_public;
+    }
+
+    pub fn v(&self) -> Scalar {
+        return self.v;
+    }
+
+    pub fn s(&self) -> Scalar {
+        return self.s;
+    }
+
+    //The public part, v and s
+    pub fn to_bytes(&self) -> [u8; 192] {
+        let mut bytes = [0u8; 192];
+        copy(&mut bytes, &self.value_commitment_public.to_bytes());
+        copy(&mut bytes[128..], self.v.as_bytes());
+        copy(&mut bytes[160..], self.s.as_bytes());
+        return bytes;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() != 192 {
+            return Err(DecodeError::WrongLength);
+        }
+        return Ok(ValueCommitment {
+            value_commitment_public: ValueCommitmentPublic::from_bytes(&bytes[0..128])?,
+            v: read_scalar(&bytes[128..])?,
+            s: read_scalar(&bytes[160..])?,
+        });
+    }
+}
+
+#[allow(non_snake_case)]
+impl BlindingSignature {
+    pub fn new(
+        value_commitment_public: ValueCommitmentPublic,
+        proof: Proof,
+        I_: EdwardsPoint,
+    ) -> Self {
+        BlindingSignature {
+            value_commitment_public: value_commitment_public,
+            proof: proof,
+            I_: I_,
+        }
+    }
+
+    //The commitment the value moved to
+    pub fn value_commitment_public(&self) -> &ValueCommitmentPublic {
+        return &self.value_commitment_public;
+    }
+
+    pub fn proof(&self) -> &Proof {
+        return &self.proof;
+    }
+
+    pub fn I_(&self) -> EdwardsPoint {
+        return self.I_;
+    }
+
+    //The commitment, I_ and the proof, 64 bytes per input commitment
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(160 + 64 * self.proof.num_tuples());
+        bytes.extend_from_slice(&self.value_commitment_public.to_bytes());
+        bytes.extend_from_slice(&self.I_.compress().to_bytes());
+        bytes.extend_from_slice(&self.proof.to_bytes());
+        return bytes;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() < 160 {
+            return Err(DecodeError::WrongLength);
+        }
+        return Ok(BlindingSignature {
+            value_commitment_public: ValueCommitmentPublic::from_bytes(&bytes[0..128])?,
+            I_: read_point(&bytes[128..])?,
+            proof: Proof::from_bytes(&bytes[160..])?,
+        });
+    }
+}
+
 fn transform_blinding_key(vc: ValueCommitment, ss: Scalar) -> ValueCommitment {
     let t = ss * vc.s.invert();
     let vcp = vc.value_commitment_public;
@@ -53,10 +205,13 @@ fn transform_blinding_key(vc: ValueCommitment, ss: Scalar) -> ValueCommitment {
     }
 }
 
+//The message, e.g. the recipient or an order id, is part of the challenge of the proof so the
+//signature cannot be replayed for another one
 #[allow(non_snake_case)]
 pub fn verify_blinding_signatures(
     input_commitments: &[ValueCommitmentPublic],
     signature: &BlindingSignature,
+    message: &[u8],
 ) -> bool {
     let S_ = signature.value_commitment_public.S;
     let V_ = signature.value_commitment_public.V;
@@ -77,7 +232,7 @@ pub fn verify_blinding_signatures(
         });
         tuples.push(VectorTuple { values: vt });
     }
-    verify_zkplmt(&tuples, &signature.proof)
+    verify_zkplmt_with_message(&tuples, &signature.proof, message)
 }
 
 #[allow(non_snake_case)]
@@ -87,6 +242,7 @@ pub fn create_blinding_signature(
     s: Scalar,
     ss: Scalar,
     k: usize,
+    message: &[u8],
 ) -> BlindingSignature {
     let transformed_commitment = transform_blinding_key(
         ValueCommitment {
@@ -118,12 +274,300 @@ pub fn create_blinding_signature(
     }
 
     BlindingSignature {
-        proof: create_zkplmt(&tuples, k, t),
+        proof: create_zkplmt_with_message(&tuples, k, t, message),
         value_commitment_public: transformed_commitment.value_commitment_public,
         I_: I,
     }
 }
 
+//A blinding signature with a tag J = ss*I_ = s*H(A) of the input commitment, the same for
+//every signature over it whatever ss is. S_ = ss*G, so a zkplmt over (G, S_), (I_, J) shows
+//J is the tag of the signature.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[allow(non_snake_case)]
+pub struct LinkableBlindingSignature {
+    signature: BlindingSignature,
+    J: EdwardsPoint,
+    tag_proof: Proof,
+}
+
+impl LinkableBlindingSignature {
+    pub fn signature(&self) -> &BlindingSignature {
+        return &self.signature;
+    }
+
+    pub fn tag(&self) -> EdwardsPoint {
+        return self.J;
+    }
+}
+
+#[allow(non_snake_case)]
+fn tag_tuples(signature: &BlindingSignature, J: EdwardsPoint) -> [VectorTuple; 1] {
+    return [VectorTuple {
+        values: vec![
+            CurveVector {
+                x: get_G(),
+                y: signature.value_commitment_public.S,
+            },
+            CurveVector {
+                x: signature.I_,
+                y: J,
+            },
+        ],
+    }];
+}
+
+pub fn create_linkable_blinding_signature(
+    input_commitments: &mut [ValueCommitmentPublic],
+    v: Scalar,
+    s: Scalar,
+    ss: Scalar,
+    k: usize,
+    message: &[u8],
+) -> LinkableBlindingSignature {
+    let signature = create_blinding_signature(input_commitments, v, s, ss, k, message);
+    let tag = ss * signature.I_;
+    let tag_proof = create_zkplmt_with_message(&tag_tuples(&signature, tag), 0, ss, message);
+    LinkableBlindingSignature {
+        signature: signature,
+        J: tag,
+        tag_proof: tag_proof,
+    }
+}
+
+pub fn verify_linkable_blinding_signature(
+    input_commitments: &[ValueCommitmentPublic],
+    signature: &LinkableBlindingSignature,
+    message: &[u8],
+) -> bool {
+    return verify_blinding_signatures(input_commitments, &signature.signature, message)
+        && verify_zkplmt_with_message(
+            &tag_tuples(&signature.signature, signature.J),
+            &signature.tag_proof,
+            message,
+        );
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SpendError {
+    InvalidSignature,
+    //a signature over the same input commitment was accepted before
+    AlreadySpent,
+}
+
+//The tags of the accepted linkable signatures
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpentTagSet {
+    tags: BTreeSet<[u8; 32]>,
+}
+
+impl SpentTagSet {
+    pub fn new() -> SpentTagSet {
+        SpentTagSet {
+            tags: BTreeSet::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        return self.tags.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.tags.is_empty();
+    }
+
+    pub fn is_spent(&self, tag: &EdwardsPoint) -> bool {
+        return self.tags.contains(&tag.compress().to_bytes());
+    }
+
+    //Verifies the signature and records its tag, nothing is recorded on error
+    pub fn spend(
+        &mut self,
+        input_commitments: &[ValueCommitmentPublic],
+        signature: &LinkableBlindingSignature,
+        message: &[u8],
+    ) -> Result<(), SpendError> {
+        if !verify_linkable_blinding_signature(input_commitments, signature, message) {
+            return Err(SpendError::InvalidSignature);
+        }
+        if !self.tags.insert(signature.J.compress().to_bytes()) {
+            return Err(SpendError::AlreadySpent);
+        }
+        return Ok(());
+    }
+}
+
+//Moves the value of some commitments to new ones, e.g. to split a commitment into
+//denominations and change or to merge several into one. With u = s^-1 of every commitment,
+//v*K = u*V, so the values add up when the sum of u*V over the inputs minus the outputs is
+//zero. Together with u*S = G for each commitment these relations are linear in the u and
+//proved by a single Schnorr proof. Each output also has a range proof of v < 2^n, n being the
+//number of range bases, so no output is negative.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ValueTransferProof {
+    e: Scalar,
+    z: Vec<Scalar>,
+    range_proofs: Vec<RangeProof>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ValueTransferError {
+    NoCommitments,
+    //the output values do not add up to the input values
+    ValuesDoNotAddUp,
+    Range(RangeProofInputError),
+}
+
+#[allow(non_snake_case)]
+fn value_transfer_challenge(
+    inputs: &[ValueCommitmentPublic],
+    outputs: &[ValueCommitmentPublic],
+    T: &[EdwardsPoint],
+) -> Scalar {
+    let mut hash_input = Vec::with_capacity(128 * (inputs.len() + outputs.len()) + 32 * T.len());
+    for c in inputs.iter().chain(outputs.iter()) {
+        hash_input.extend_from_slice(&c.to_bytes());
+    }
+    for point in T {
+        hash_input.extend_from_slice(&point.compress().to_bytes());
+    }
+    return Scalar::hash_from_bytes::<Sha512>(&hash_input);
+}
+
+//The terms of u*V, negated for the outputs
+fn value_transfer_sum_points(
+    inputs: &[ValueCommitmentPublic],
+    outputs: &[ValueCommitmentPublic],
+) -> Vec<EdwardsPoint> {
+    return inputs
+        .iter()
+        .map(|c| c.V)
+        .chain(outputs.iter().map(|c| -c.V))
+        .collect();
+}
+
+#[allow(non_snake_case)]
+fn create_value_transfer_proof(
+    inputs: &[ValueCommitment],
+    outputs: &[ValueCommitment],
+    range_bases: &[EdwardsPoint],
+) -> Result<ValueTransferProof, ValueTransferError> {
+    if inputs.len() == 0 || outputs.len() == 0 {
+        return Err(ValueTransferError::NoCommitments);
+    }
+    let sum = |commitments: &[ValueCommitment]| {
+        commitments.iter().fold(Scalar::zero(), |sum, c| sum + c.v)
+    };
+    if sum(inputs) != sum(outputs) {
+        return Err(ValueTransferError::ValuesDoNotAddUp);
+    }
+    let mut range_proofs = Vec::new();
+    for c in outputs {
+        range_proofs
+            .push(create_range_proof(c.v, c.s, range_bases).map_err(ValueTransferError::Range)?);
+    }
+
+    let commitments: Vec<ValueCommitment> = inputs.iter().chain(outputs.iter()).cloned().collect();
+    let public_inputs: Vec<ValueCommitmentPublic> =
+        inputs.iter().map(|c| c.value_commitment_public).collect();
+    let public_outputs: Vec<ValueCommitmentPublic> =
+        outputs.iter().map(|c| c.value_commitment_public).collect();
+    let mut csprng: OsRng = OsRng::default();
+    let k: Vec<Scalar> = commitments
+        .iter()
+        .map(|_| Scalar::random(&mut csprng))
+        .collect();
+    let mut T: Vec<EdwardsPoint> = commitments
+        .iter()
+        .zip(k.iter())
+        .map(|(c, k)| k * c.value_commitment_public.S)
+        .collect();
+    T.push(EdwardsPoint::multiscalar_mul(
+        &k,
+        &value_transfer_sum_points(&public_inputs, &public_outputs),
+    ));
+    let e = value_transfer_challenge(&public_inputs, &public_outputs, &T);
+    let z = commitments
+        .iter()
+        .zip(k.iter())
+        .map(|(c, k)| k + e * c.s.invert())
+        .collect();
+    return Ok(ValueTransferProof {
+        e: e,
+        z: z,
+        range_proofs: range_proofs,
+    });
+}
+
+#[allow(non_snake_case)]
+pub fn verify_value_transfer(
+    inputs: &[ValueCommitmentPublic],
+    outputs: &[ValueCommitmentPublic],
+    range_bases: &[EdwardsPoint],
+    proof: &ValueTransferProof,
+) -> bool {
+    if inputs.len() == 0
+        || outputs.len() == 0
+        || proof.z.len() != inputs.len() + outputs.len()
+        || proof.range_proofs.len() != outputs.len()
+    {
+        return false;
+    }
+    for (c, range_proof) in outputs.iter().zip(proof.range_proofs.iter()) {
+        if range_proof.S() != c.S
+            || !verify_range_proof(c.V, range_bases, range_bases.len(), range_proof)
+        {
+            return false;
+        }
+    }
+    let G = get_G();
+    let mut T: Vec<EdwardsPoint> = inputs
+        .iter()
+        .chain(outputs.iter())
+        .zip(proof.z.iter())
+        .map(|(c, z)| EdwardsPoint::multiscalar_mul(&[*z, -proof.e], &[c.S, G]))
+        .collect();
+    T.push(EdwardsPoint::multiscalar_mul(
+        &proof.z,
+        &value_transfer_sum_points(inputs, outputs),
+    ));
+    return proof.e == value_transfer_challenge(inputs, outputs, &T);
+}
+
+//Splits the commitment into one per (value, A, B) of parts with fresh blinding keys, the
+//values must add up to the value of the commitment
+#[allow(non_snake_case)]
+pub fn split_value_commitment(
+    commitment: &ValueCommitment,
+    parts: &[(u64, EdwardsPoint, EdwardsPoint)],
+    range_bases: &[EdwardsPoint],
+) -> Result<(Vec<ValueCommitment>, ValueTransferProof), ValueTransferError> {
+    let mut csprng: OsRng = OsRng::default();
+    let outputs: Vec<ValueCommitment> = parts
+        .iter()
+        .map(|(v, A, B)| {
+            ValueCommitment::new(Scalar::from(*v), Scalar::random(&mut csprng), *A, *B)
+        })
+        .collect();
+    let proof = create_value_transfer_proof(&[*commitment], &outputs, range_bases)?;
+    return Ok((outputs, proof));
+}
+
+//Merges the commitments into one for the one time key (A, B)
+#[allow(non_snake_case)]
+pub fn merge_value_commitments(
+    commitments: &[ValueCommitment],
+    A: EdwardsPoint,
+    B: EdwardsPoint,
+    range_bases: &[EdwardsPoint],
+) -> Result<(ValueCommitment, ValueTransferProof), ValueTransferError> {
+    let mut csprng: OsRng = OsRng::default();
+    let v = commitments.iter().fold(Scalar::zero(), |sum, c| sum + c.v);
+    let output = ValueCommitment::new(v, Scalar::random(&mut csprng), A, B);
+    let proof = create_value_transfer_proof(commitments, &[output], range_bases)?;
+    return Ok((output, proof));
+}
+
 fn create_random_divisions(sum: Scalar, count: usize) -> Vec<Scalar> {
     let mut csprng: OsRng = OsRng::default();
     let mut output = Vec::new();
@@ -220,7 +664,218 @@ mod tests {
         }
 
         let ss = Scalar::random(&mut csprng);
-        let signature = create_blinding_signature(&mut input_tuples, v, s, ss, 3);
-        assert!(verify_blinding_signatures(&input_tuples, &signature));
+        let signature = create_blinding_signature(&mut input_tuples, v, s, ss, 3, b"");
+        assert!(verify_blinding_signatures(&input_tuples, &signature, b""));
+
+        let signature =
+            create_blinding_signature(&mut input_tuples, v, s, ss, 3, b"order 42 to bob");
+        assert!(verify_blinding_signatures(
+            &input_tuples,
+            &signature,
+            b"order 42 to bob"
+        ));
+        assert!(!verify_blinding_signatures(
+            &input_tuples,
+            &signature,
+            b"order 42 to eve"
+        ));
+        assert!(!verify_blinding_signatures(&input_tuples, &signature, b""));
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_blinding_signature_bytes() {
+        let mut csprng: OsRng = OsRng::default();
+        let new_commitment = |v: u64| {
+            let mut csprng: OsRng = OsRng::default();
+            let A = Scalar::random(&mut csprng) * get_G();
+            let B = Scalar::random(&mut csprng) * A;
+            ValueCommitment::new(Scalar::from(v), Scalar::random(&mut csprng), A, B)
+        };
+        let commitments: Vec<ValueCommitment> = (0..4).map(|i| new_commitment(100 * i)).collect();
+        let mut inputs: Vec<ValueCommitmentPublic> =
+            commitments.iter().map(|c| *c.public()).collect();
+        assert_eq!(commitments[2].public().S(), commitments[2].s() * get_G());
+        assert_eq!(
+            ValueCommitment::from_bytes(&commitments[2].to_bytes()),
+            Ok(commitments[2])
+        );
+
+        let signature = create_blinding_signature(
+            &mut inputs,
+            commitments[2].v(),
+            commitments[2].s(),
+            Scalar::random(&mut csprng),
+            2,
+            b"",
+        );
+
+        //what another service receives
+        let inputs_bytes: Vec<[u8; 128]> = inputs.iter().map(|c| c.to_bytes()).collect();
+        let signature_bytes = signature.to_bytes();
+        assert_eq!(signature_bytes.len(), 160 + 64 * 4);
+        let received_inputs: Vec<ValueCommitmentPublic> = inputs_bytes
+            .iter()
+            .map(|bytes| ValueCommitmentPublic::from_bytes(bytes).unwrap())
+            .collect();
+        let received = BlindingSignature::from_bytes(&signature_bytes).unwrap();
+        assert_eq!(received, signature);
+        assert!(verify_blinding_signatures(&received_inputs, &received, b""));
+
+        let serialized = bincode::serialize(&signature).unwrap();
+        let deserialized: BlindingSignature = bincode::deserialize(&serialized).unwrap();
+        assert!(verify_blinding_signatures(
+            &received_inputs,
+            &deserialized,
+            b""
+        ));
+
+        assert_eq!(
+            BlindingSignature::from_bytes(&signature_bytes[0..signature_bytes.len() - 32]),
+            Err(DecodeError::WrongLength)
+        );
+        let mut bad = signature_bytes.clone();
+        bad[160..192].copy_from_slice(&[0xffu8; 32]);
+        assert_eq!(
+            BlindingSignature::from_bytes(&bad),
+            Err(DecodeError::NonCanonicalScalar)
+        );
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_spent_tag_set() {
+        let mut csprng: OsRng = OsRng::default();
+        let commitments: Vec<ValueCommitment> = (0..3)
+            .map(|_| {
+                let A = Scalar::random(&mut csprng) * get_G();
+                let B = Scalar::random(&mut csprng) * A;
+                ValueCommitment::new(Scalar::from(10u64), Scalar::random(&mut csprng), A, B)
+            })
+            .collect();
+        let inputs: Vec<ValueCommitmentPublic> = commitments.iter().map(|c| *c.public()).collect();
+        let sign = |k: usize| {
+            create_linkable_blinding_signature(
+                &mut inputs.clone(),
+                commitments[k].v(),
+                commitments[k].s(),
+                Scalar::random(&mut OsRng::default()),
+                k,
+                b"height 100",
+            )
+        };
+        let first = sign(1);
+        let second = sign(1);
+        let other = sign(2);
+        assert_eq!(first.tag(), second.tag());
+        assert_ne!(first.tag(), other.tag());
+        assert_ne!(
+            first.signature().value_commitment_public(),
+            second.signature().value_commitment_public()
+        );
+
+        let mut spent = SpentTagSet::new();
+        assert_eq!(spent.spend(&inputs, &first, b"height 100"), Ok(()));
+        assert!(spent.is_spent(&second.tag()));
+        assert_eq!(
+            spent.spend(&inputs, &second, b"height 100"),
+            Err(SpendError::AlreadySpent)
+        );
+        assert_eq!(
+            spent.spend(&inputs, &other, b"height 101"),
+            Err(SpendError::InvalidSignature)
+        );
+        assert_eq!(spent.spend(&inputs, &other, b"height 100"), Ok(()));
+        assert_eq!(spent.len(), 2);
+
+        let mut forged = sign(0);
+        forged.J = first.tag();
+        assert_eq!(
+            spent.spend(&inputs, &forged, b"height 100"),
+            Err(SpendError::InvalidSignature)
+        );
+        forged.J = forged.J + get_G();
+        let mut fresh = SpentTagSet::new();
+        assert_eq!(
+            fresh.spend(&inputs, &forged, b"height 100"),
+            Err(SpendError::InvalidSignature)
+        );
+        assert!(fresh.is_empty());
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn test_split_and_merge() {
+        let mut csprng: OsRng = OsRng::default();
+        let mut range_bases: Vec<EdwardsPoint> = (0..15)
+            .map(|_| Scalar::random(&mut csprng) * get_G())
+            .collect();
+        let sum = range_bases
+            .iter()
+            .fold(EdwardsPoint::default(), |X, Y| X + Y);
+        range_bases.push(-sum);
+        let key = || {
+            let A = Scalar::random(&mut OsRng::default()) * get_G();
+            (A, Scalar::random(&mut OsRng::default()) * A)
+        };
+
+        let (A, B) = key();
+        let commitment =
+            ValueCommitment::new(Scalar::from(1000u64), Scalar::random(&mut csprng), A, B);
+        let parts: Vec<(u64, EdwardsPoint, EdwardsPoint)> = [500u64, 200, 200, 100]
+            .iter()
+            .map(|v| {
+                let (A, B) = key();
+                (*v, A, B)
+            })
+            .collect();
+        let (outputs, proof) = split_value_commitment(&commitment, &parts, &range_bases).unwrap();
+        let public_outputs: Vec<ValueCommitmentPublic> =
+            outputs.iter().map(|c| *c.public()).collect();
+        assert!(verify_value_transfer(
+            &[*commitment.public()],
+            &public_outputs,
+            &range_bases,
+            &proof
+        ));
+        assert!(!verify_value_transfer(
+            &[*commitment.public()],
+            &public_outputs[0..3],
+            &range_bases,
+            &proof
+        ));
+
+        let (A, B) = key();
+        let (merged, proof) = merge_value_commitments(&outputs[1..], A, B, &range_bases).unwrap();
+        assert_eq!(merged.v(), Scalar::from(500u64));
+        assert!(verify_value_transfer(
+            &public_outputs[1..],
+            &[*merged.public()],
+            &range_bases,
+            &proof
+        ));
+        assert!(!verify_value_transfer(
+            &public_outputs[0..3],
+            &[*merged.public()],
+            &range_bases,
+            &proof
+        ));
+
+        //values that do not add up or a negative part
+        let mut wrong = parts.clone();
+        wrong[0].0 = 501;
+        assert_eq!(
+            split_value_commitment(&commitment, &wrong, &range_bases).err(),
+            Some(ValueTransferError::ValuesDoNotAddUp)
+        );
+        let minus_one = ValueCommitment::new(-Scalar::one(), Scalar::random(&mut csprng), A, B);
+        let one_more =
+            ValueCommitment::new(Scalar::from(1001u64), Scalar::random(&mut csprng), A, B);
+        assert_eq!(
+            create_value_transfer_proof(&[commitment], &[one_more, minus_one], &range_bases).err(),
+            Some(ValueTransferError::Range(
+                RangeProofInputError::ValueOutOfRange
+            ))
+        );
     }
 }